@@ -0,0 +1,22 @@
+// Universal Hive Protocol engine, reading commands from stdin and answering on stdout
+use std::io::{self, BufRead, Write};
+
+use hive_rust::uhp::Engine;
+
+fn main() {
+    let mut engine = Engine::default();
+    let mut stdout = io::stdout();
+
+    // Engines introduce themselves when they start
+    writeln!(stdout, "{}", engine.handle("info")).unwrap();
+
+    for line in io::stdin().lock().lines() {
+        let line = line.unwrap(); // TODO: remove unwrap
+        if line.trim() == "exit" {
+            break;
+        }
+
+        writeln!(stdout, "{}", engine.handle(&line)).unwrap();
+        stdout.flush().unwrap();
+    }
+}
@@ -1,25 +1,37 @@
 use std::{
     collections::{HashMap, HashSet},
-    ops::Sub,
+    error::Error,
 };
 
-use crate::coordinate::HexagonalCoordinateSystem;
+use crate::coordinate::{HexagonalCoordinateSystem, XYCoordinate, SYMMETRIES};
+use crate::rng::splitmix;
 
 #[derive(PartialEq, Clone)]
-pub(crate) struct StackableHexagonalBoard<P, CS, C>
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(bound(
+        serialize = "P: serde::Serialize, CS: serde::Serialize, C: serde::Serialize",
+        deserialize = "P: serde::Deserialize<'de>, CS: serde::Deserialize<'de>, C: serde::Deserialize<'de>"
+    ))
+)]
+pub struct StackableHexagonalBoard<P, CS, C>
 where
     CS: HexagonalCoordinateSystem<Coordinate = C>,
     C: std::hash::Hash + std::cmp::Eq,
 {
+    #[cfg_attr(feature = "serde", serde(with = "cells"))]
     cells: HashMap<C, Cell<P>>,
-    pub(crate) coordinate_system: CS,
+    pub coordinate_system: CS,
+    // Zobrist hash of the stacks, every piece xored in with its coordinate and its height
+    hash: u64,
 }
 
 struct PieceGuard<'a, P, CS, C>
 where
     CS: HexagonalCoordinateSystem<Coordinate = C>,
-    C: PartialEq + std::hash::Hash + std::cmp::Eq + Clone + Copy,
-    P: Clone,
+    C: PartialEq + std::hash::Hash + std::cmp::Eq + Clone + Copy + Zobrist,
+    P: Clone + Zobrist,
 {
     board: &'a mut StackableHexagonalBoard<P, CS, C>,
     piece: P,
@@ -29,8 +41,8 @@ where
 impl<'a, P, CS, C> Drop for PieceGuard<'a, P, CS, C>
 where
     CS: HexagonalCoordinateSystem<Coordinate = C>,
-    C: PartialEq + std::hash::Hash + std::cmp::Eq + Clone + Copy,
-    P: Clone, // TODO: is it possible to remove this? we want to move the ownership back to the board
+    C: PartialEq + std::hash::Hash + std::cmp::Eq + Clone + Copy + Zobrist,
+    P: Clone + Zobrist, // TODO: is it possible to remove this? we want to move the ownership back to the board
 {
     fn drop(&mut self) {
         self.board.put_piece(self.piece.clone(), self.coordinate);
@@ -39,23 +51,54 @@ where
 
 type Cell<T> = Vec<T>;
 
+// Coordinates can't be map keys in most formats, so the cells go as a list of coordinates and their stacks
+#[cfg(feature = "serde")]
+mod cells {
+    use std::collections::HashMap;
+    use std::hash::Hash;
+
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    use super::Cell;
+
+    pub fn serialize<S, C, P>(cells: &HashMap<C, Cell<P>>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+        C: Serialize,
+        P: Serialize,
+    {
+        serializer.collect_seq(cells)
+    }
+
+    pub fn deserialize<'de, D, C, P>(deserializer: D) -> Result<HashMap<C, Cell<P>>, D::Error>
+    where
+        D: Deserializer<'de>,
+        C: Deserialize<'de> + Hash + Eq,
+        P: Deserialize<'de>,
+    {
+        Ok(Vec::<(C, Cell<P>)>::deserialize(deserializer)?
+            .into_iter()
+            .collect())
+    }
+}
+
 impl<P, CS, C> StackableHexagonalBoard<P, CS, C>
 where
     CS: HexagonalCoordinateSystem<Coordinate = C>,
-    C: PartialEq + std::hash::Hash + std::cmp::Eq + Clone + Copy,
-    P: Clone,
+    C: PartialEq + std::hash::Hash + std::cmp::Eq + Clone + Copy + Zobrist,
+    P: Clone + Zobrist,
 {
-    pub(crate) fn new(cs: CS) -> Self {
+    pub fn new(cs: CS) -> Self {
         StackableHexagonalBoard {
             cells: HashMap::new(),
             coordinate_system: cs,
+            hash: 0,
         }
     }
 
     // examine takes a piece from the board and returns a guard that will put the piece back on drop
-    fn examine(&mut self, coordinate: C) -> Option<PieceGuard<P, CS, C>> {
-        let from_cell = self.cells.get_mut(&coordinate)?;
-        let piece = from_cell.pop()?;
+    fn examine(&mut self, coordinate: C) -> Option<PieceGuard<'_, P, CS, C>> {
+        let piece = self.take_top_piece(coordinate)?;
 
         Some(PieceGuard {
             board: self,
@@ -64,15 +107,26 @@ where
         })
     }
 
-    pub(crate) fn get_cell(&self, coordinate: C) -> Option<&Cell<P>> {
+    // Runs the given function with the top piece at the given position taken out of the board
+    pub fn lifting<F, R>(&mut self, coordinate: C, f: F) -> Option<R>
+    where
+        F: FnOnce(&Self) -> R,
+    {
+        let piece_guard = self.examine(coordinate)?;
+        Some(f(piece_guard.board))
+    }
+
+    pub fn get_cell(&self, coordinate: C) -> Option<&Cell<P>> {
         self.cells.get(&coordinate)
     }
 
-    pub(crate) fn get_top_piece(&self, coordinate: C) -> Option<&P> {
+    pub fn get_top_piece(&self, coordinate: C) -> Option<&P> {
         self.get_cell(coordinate)?.last()
     }
 
-    pub(crate) fn put_piece(&mut self, p: P, coordinate: C) {
+    pub fn put_piece(&mut self, p: P, coordinate: C) {
+        self.hash ^= zobrist_key(&p, coordinate, self.height(coordinate) + 1);
+
         match self.cells.get_mut(&coordinate) {
             None => {
                 let cell = vec![p];
@@ -82,12 +136,22 @@ where
         }
     }
 
-    pub(crate) fn move_top_piece(&mut self, from: C, to: C) -> Result<(), String> {
-        let from_cell = self
-            .cells
-            .get_mut(&from)
-            .ok_or("Could not get cell 'from'")?;
-        let piece = from_cell.pop().ok_or("'from' cell is empty")?;
+    // Removes the top piece of a cell, forgetting the cell once it's empty
+    pub fn take_top_piece(&mut self, coordinate: C) -> Option<P> {
+        let cell = self.cells.get_mut(&coordinate)?;
+        let height = cell.len();
+        let piece = cell.pop()?;
+        self.hash ^= zobrist_key(&piece, coordinate, height);
+
+        if cell.is_empty() {
+            self.cells.remove(&coordinate);
+        }
+
+        Some(piece)
+    }
+
+    pub fn move_top_piece(&mut self, from: C, to: C) -> Result<(), String> {
+        let piece = self.take_top_piece(from).ok_or("'from' cell is empty")?;
 
         self.put_piece(piece, to);
         Ok(())
@@ -106,14 +170,14 @@ where
             .collect()
     }
 
-    pub(crate) fn neighbor_pieces(&self, coordinate: C) -> Vec<&P> {
+    pub fn neighbor_pieces(&self, coordinate: C) -> Vec<&P> {
         self.neighbors(coordinate)
             .iter()
             .map(|(_, piece)| *piece)
             .collect()
     }
 
-    pub(crate) fn hive(&self) -> HashSet<C> {
+    pub fn hive(&self) -> HashSet<C> {
         HashSet::from_iter(
             self.cells
                 .iter()
@@ -121,39 +185,138 @@ where
         )
     }
 
-    pub(crate) fn hive_without(&mut self, coordinate: C) -> HashSet<C> {
+    pub fn hive_without(&mut self, coordinate: C) -> HashSet<C> {
         let piece_guard = self.examine(coordinate).unwrap();
         piece_guard.board.hive()
     }
 
-    pub(crate) fn occupied_amount(&self) -> usize {
-        self.cells.len()
+    pub fn height(&self, coordinate: C) -> usize {
+        self.get_cell(coordinate).map_or(0, |cell| cell.len())
     }
 
-    // Returns the outline walkable cells without taking into account the top piece at the position given
-    pub(crate) fn walkable_without(&mut self, coordinate: C) -> HashSet<C> {
-        let hive_without = self.hive_without(coordinate);
-
-        let neighbors: HashSet<C> = hive_without
+    // Cells with more pieces stacked than the given height
+    pub fn taller_than(&self, height: usize) -> HashSet<C> {
+        self.cells
             .iter()
-            .flat_map(|&c| self.coordinate_system.neighbor_coordinates(c))
-            .collect();
+            .filter(|(_, cell)| cell.len() > height)
+            .map(|(&c, _)| c)
+            .collect()
+    }
+
+    // Determine if a piece can go from one cell to a neighbor one, climbing up or down if needed
+    // It can't squeeze between two stacks taller than both the origin and the destination
+    // The moving piece must have been taken out of the board
+    pub fn can_step(&self, from: C, to: C) -> Result<bool, Box<dyn Error>> {
+        let level = self.height(from).max(self.height(to));
 
-        neighbors.sub(&hive_without)
+        self.coordinate_system
+            .can_slide(from, to, &self.taller_than(level))
     }
 
-    pub(crate) fn hive_and_walkable_without(&mut self, coordinate: C) -> HashSet<C> {
-        let hive_without = self.hive_without(coordinate);
+    // Empty neighbor cells a piece on the ground can crawl to in a single step
+    // It has to slide between the cells of the given hive without going through a gate,
+    // and keep touching the hive all along the way
+    pub fn crawl_steps(&self, from: C, hive: &HashSet<C>) -> HashSet<C> {
+        let around_from = self.coordinate_system.neighbor_coordinates(from);
 
-        let neighbors: HashSet<C> = hive_without
-            .iter()
-            .flat_map(|&c| self.coordinate_system.neighbor_coordinates(c))
-            .collect();
+        around_from
+            .into_iter()
+            .filter(|to| !hive.contains(to))
+            .filter(|&to| {
+                let touching_hive = self
+                    .coordinate_system
+                    .neighbor_coordinates(to)
+                    .iter()
+                    .any(|c| around_from.contains(c) && hive.contains(c));
+
+                touching_hive
+                    && self
+                        .coordinate_system
+                        .can_slide(from, to, hive)
+                        .unwrap_or(false)
+            })
+            .collect()
+    }
+
+    // Cells whose top piece can't leave without splitting the hive in two
+    // Only lone pieces can be pinned, a piece on top of a stack leaves the rest of the stack behind
+    pub fn pinned(&self) -> HashSet<C> {
+        self.articulation_points()
+            .into_iter()
+            .filter(|&c| self.height(c) == 1)
+            .collect()
+    }
+
+    // Tarjan's algorithm over the cells of the hive
+    fn articulation_points(&self) -> HashSet<C> {
+        let hive = self.hive();
+
+        let mut discovery = HashMap::new();
+        let mut low = HashMap::new();
+        let mut points = HashSet::new();
+
+        for &root in &hive {
+            if !discovery.contains_key(&root) {
+                self.visit(root, None, &hive, &mut discovery, &mut low, &mut points);
+            }
+        }
+
+        points
+    }
+
+    // Depth first search step of the articulation points, returns the lowest discovery time reachable from 'coordinate'
+    fn visit(
+        &self,
+        coordinate: C,
+        parent: Option<C>,
+        hive: &HashSet<C>,
+        discovery: &mut HashMap<C, usize>,
+        low: &mut HashMap<C, usize>,
+        points: &mut HashSet<C>,
+    ) -> usize {
+        let time = discovery.len();
+        discovery.insert(coordinate, time);
+        low.insert(coordinate, time);
+
+        let mut children = 0;
+        for neighbor in self.coordinate_system.neighbor_coordinates(coordinate) {
+            if !hive.contains(&neighbor) || Some(neighbor) == parent {
+                continue;
+            }
+
+            if let Some(&neighbor_time) = discovery.get(&neighbor) {
+                low.insert(coordinate, low[&coordinate].min(neighbor_time));
+                continue;
+            }
+
+            children += 1;
+            let neighbor_low = self.visit(neighbor, Some(coordinate), hive, discovery, low, points);
+            low.insert(coordinate, low[&coordinate].min(neighbor_low));
+
+            // Nothing under the neighbor goes around 'coordinate' to reach above it
+            if parent.is_some() && neighbor_low >= time {
+                points.insert(coordinate);
+            }
+        }
+
+        // The root splits the hive when more than one branch of the search starts from it
+        if parent.is_none() && children > 1 {
+            points.insert(coordinate);
+        }
 
-        neighbors.union(&hive_without).copied().collect()
+        low[&coordinate]
     }
 
-    pub(crate) fn find<F>(&self, filter: F) -> Vec<C>
+    // Same stacks, same hash, however they got there
+    pub fn hash(&self) -> u64 {
+        self.hash
+    }
+
+    pub fn occupied_amount(&self) -> usize {
+        self.cells.len()
+    }
+
+    pub fn find<F>(&self, filter: F) -> Vec<C>
     where
         F: Fn(&P) -> bool,
     {
@@ -166,6 +329,61 @@ where
     }
 }
 
+// The stacks of a board, each under the cell it's in, sorted
+pub type CanonicalKey<P> = Vec<(XYCoordinate, Cell<P>)>;
+
+impl<P, CS> StackableHexagonalBoard<P, CS, XYCoordinate>
+where
+    CS: HexagonalCoordinateSystem<Coordinate = XYCoordinate>,
+    P: Clone + Zobrist + Ord,
+{
+    // The same for boards that only differ in where the hive is and how it's turned or mirrored
+    // Out of every symmetry of the stacks, moved so that the first cell is at the origin, the smallest one is taken
+    pub fn canonical_key(&self) -> CanonicalKey<P> {
+        (0..SYMMETRIES)
+            .map(|symmetry| {
+                let mut stacks: CanonicalKey<P> = self
+                    .cells
+                    .iter()
+                    .map(|(&c, cell)| (c.symmetric(symmetry), cell.clone()))
+                    .collect();
+                stacks.sort();
+
+                if let Some(&(origin, _)) = stacks.first() {
+                    for (c, _) in stacks.iter_mut() {
+                        *c = *c - origin;
+                    }
+                }
+                stacks
+            })
+            .min()
+            .unwrap_or_default()
+    }
+
+    // The hash of the stacks as they are in the canonical key
+    pub fn canonical_hash(&self) -> u64 {
+        self.canonical_key()
+            .iter()
+            .flat_map(|(c, cell)| {
+                cell.iter()
+                    .enumerate()
+                    .map(|(index, piece)| zobrist_key(piece, *c, index + 1))
+            })
+            .fold(0, |hash, key| hash ^ key)
+    }
+}
+
+// Pieces and coordinates as a number mixed from their fields, which their Zobrist keys are derived from
+pub trait Zobrist {
+    fn zobrist_id(&self) -> u64;
+}
+
+// The key xored into the hash of the stacks for a piece at the given coordinate and height
+// Keys are derived instead of drawn from a table, so they exist for every coordinate and never change
+pub fn zobrist_key<P: Zobrist, C: Zobrist>(piece: &P, coordinate: C, height: usize) -> u64 {
+    splitmix(&[piece.zobrist_id(), coordinate.zobrist_id(), height as u64])
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -184,22 +402,27 @@ mod tests {
         let black_bee = piece::Piece {
             bug: Bee,
             color: Black,
+            number: 1,
         };
         let white_bee = piece::Piece {
             bug: Bee,
             color: White,
+            number: 1,
         };
         let black_beetle = piece::Piece {
             bug: Beetle,
             color: Black,
+            number: 1,
         };
         let white_beetle = piece::Piece {
             bug: Beetle,
             color: White,
+            number: 1,
         };
         let black_ant = piece::Piece {
             bug: Ant,
             color: Black,
+            number: 1,
         };
 
         board.put_piece(black_bee.clone(), XYCoordinate { x: 0, y: 0 });
@@ -230,4 +453,190 @@ mod tests {
 
         assert!(board.move_top_piece((0, 50).into(), (0, 0).into()).is_err());
     }
+
+    #[test]
+    fn steps_through_gates() {
+        use piece::Bug::*;
+        use piece::Color::*;
+        let mut board = StackableHexagonalBoard::new(GenericCoordinateSystem::new(
+            RELATIVE_NEIGHBORS_CLOCKWISE,
+        ));
+        let black_bee = piece::Piece {
+            bug: Bee,
+            color: Black,
+            number: 1,
+        };
+        let white_beetle = piece::Piece {
+            bug: Beetle,
+            color: White,
+            number: 1,
+        };
+
+        board.put_piece(black_bee.clone(), XYCoordinate { x: 1, y: 0 });
+        board.put_piece(black_bee.clone(), XYCoordinate { x: 0, y: 1 });
+        board.put_piece(black_bee.clone(), XYCoordinate { x: 1, y: -1 });
+
+        // climbing onto (1, 0) only squeezes between stacks as tall as the destination
+        assert!(board.can_step((0, 0).into(), (1, 0).into()).unwrap());
+
+        board.put_piece(white_beetle.clone(), XYCoordinate { x: 0, y: 1 });
+
+        assert!(board.can_step((0, 0).into(), (1, 0).into()).unwrap());
+
+        board.put_piece(white_beetle.clone(), XYCoordinate { x: 1, y: -1 });
+
+        // both (0, 1) and (1, -1) are taller than the origin and the destination
+        assert!(!board.can_step((0, 0).into(), (1, 0).into()).unwrap());
+
+        board.put_piece(white_beetle.clone(), XYCoordinate { x: 1, y: 0 });
+
+        // moving onto a stack as tall as the gate is fine
+        assert!(board.can_step((0, 0).into(), (1, 0).into()).unwrap());
+    }
+
+    #[test]
+    fn pins_pieces_holding_the_hive_together() {
+        use piece::Bug::*;
+        use piece::Color::*;
+        let mut board = StackableHexagonalBoard::new(GenericCoordinateSystem::new(
+            RELATIVE_NEIGHBORS_CLOCKWISE,
+        ));
+        let black_bee = piece::Piece {
+            bug: Bee,
+            color: Black,
+            number: 1,
+        };
+        let white_beetle = piece::Piece {
+            bug: Beetle,
+            color: White,
+            number: 1,
+        };
+
+        board.put_piece(black_bee.clone(), XYCoordinate { x: 0, y: 0 });
+        board.put_piece(black_bee.clone(), XYCoordinate { x: 1, y: 0 });
+        board.put_piece(black_bee.clone(), XYCoordinate { x: 2, y: 0 });
+
+        assert_eq!(board.pinned(), HashSet::from([(1, 0).into()])); // the middle of the line
+
+        board.put_piece(black_bee.clone(), XYCoordinate { x: 3, y: 0 });
+
+        assert_eq!(
+            board.pinned(),
+            HashSet::from([(1, 0).into(), (2, 0).into()])
+        );
+
+        board.put_piece(white_beetle.clone(), XYCoordinate { x: 2, y: 0 });
+
+        // the beetle can leave, the bee under it stays
+        assert_eq!(board.pinned(), HashSet::from([(1, 0).into()]));
+
+        board.put_piece(black_bee.clone(), XYCoordinate { x: 0, y: 1 });
+        board.put_piece(black_bee.clone(), XYCoordinate { x: 1, y: 1 });
+
+        // (0, 0), (1, 0), (2, 0), (1, 1) and (0, 1) close a ring
+        assert_eq!(board.pinned(), HashSet::new());
+    }
+
+    #[test]
+    fn hash_follows_the_stacks() {
+        use piece::Bug::*;
+        use piece::Color::*;
+        let mut board = StackableHexagonalBoard::new(GenericCoordinateSystem::new(
+            RELATIVE_NEIGHBORS_CLOCKWISE,
+        ));
+        let black_bee = piece::Piece {
+            bug: Bee,
+            color: Black,
+            number: 1,
+        };
+        let white_beetle = piece::Piece {
+            bug: Beetle,
+            color: White,
+            number: 1,
+        };
+
+        assert_eq!(board.hash(), 0);
+
+        board.put_piece(black_bee.clone(), XYCoordinate { x: 0, y: 0 });
+        // The bee, its color and number, the cell and the height, see Zobrist
+        let key = splitmix(&[splitmix(&[0, 0, 1]), splitmix(&[0, 0]), 1]);
+        assert_eq!(board.hash(), key);
+        assert_eq!(key, 0xcf25879328a540e9); // keys are fixed, so stored hashes stay valid
+        board.put_piece(white_beetle.clone(), XYCoordinate { x: 1, y: 0 });
+        let beside = board.hash();
+
+        board
+            .move_top_piece(XYCoordinate { x: 1, y: 0 }, XYCoordinate { x: 0, y: 0 })
+            .unwrap();
+        let on_top = board.hash();
+        assert_ne!(on_top, beside);
+
+        board
+            .move_top_piece(XYCoordinate { x: 0, y: 0 }, XYCoordinate { x: 1, y: 0 })
+            .unwrap();
+        assert_eq!(board.hash(), beside); // back where it was
+
+        let mut other = StackableHexagonalBoard::new(GenericCoordinateSystem::new(
+            RELATIVE_NEIGHBORS_CLOCKWISE,
+        ));
+        other.put_piece(white_beetle.clone(), XYCoordinate { x: 0, y: 0 });
+        other.put_piece(black_bee.clone(), XYCoordinate { x: 0, y: 0 });
+        assert_ne!(other.hash(), on_top); // same pieces and cell, stacked the other way around
+
+        other.take_top_piece(XYCoordinate { x: 0, y: 0 });
+        other.take_top_piece(XYCoordinate { x: 0, y: 0 });
+        other.put_piece(black_bee, XYCoordinate { x: 0, y: 0 });
+        other.put_piece(white_beetle, XYCoordinate { x: 0, y: 0 });
+        assert_eq!(other.hash(), on_top);
+    }
+
+    #[test]
+    fn canonical_key_ignores_where_the_hive_is_and_how_it_is_turned() {
+        use piece::Bug::*;
+        use piece::Color::*;
+        let black_bee = piece::Piece {
+            bug: Bee,
+            color: Black,
+            number: 1,
+        };
+        let white_beetle = piece::Piece {
+            bug: Beetle,
+            color: White,
+            number: 1,
+        };
+        let white_ant = piece::Piece {
+            bug: Ant,
+            color: White,
+            number: 1,
+        };
+
+        // A bent line with the beetle on top of the bee, laid out as given by each symmetry and offset
+        let layout = |symmetry: usize, offset: XYCoordinate| {
+            let mut board = StackableHexagonalBoard::new(GenericCoordinateSystem::new(
+                RELATIVE_NEIGHBORS_CLOCKWISE,
+            ));
+            for (piece, c) in [
+                (&black_bee, (0, 0)),
+                (&white_beetle, (0, 0)),
+                (&white_ant, (1, 0)),
+                (&black_bee, (2, -1)),
+                (&white_ant, (-1, 1)),
+            ] {
+                let c = XYCoordinate::from(c).symmetric(symmetry) + offset;
+                board.put_piece(piece.clone(), c);
+            }
+            board
+        };
+
+        let key = layout(0, (0, 0).into()).canonical_key();
+        for symmetry in 0..SYMMETRIES {
+            assert_eq!(layout(symmetry, (3, -2).into()).canonical_key(), key);
+        }
+
+        let mut board = layout(0, (0, 0).into());
+        board
+            .move_top_piece(XYCoordinate { x: -1, y: 1 }, XYCoordinate { x: -1, y: 0 })
+            .unwrap();
+        assert_ne!(board.canonical_key(), key); // the line is bent the other way at the end
+    }
 }
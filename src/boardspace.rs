@@ -0,0 +1,308 @@
+// Importer for the Hive game records of boardspace.net, written in their SGF-like format
+// Every move is a property like "P0[5 dropb wA1 M 13 -wQ]": its number, a verb, the piece and the cell it ends at
+// Cells are a column letter and a row number, where the columns go right and the rows go down diagonally
+// The move also tells where the piece goes in standard notation, which has to be the same cell
+
+use crate::coordinate::XYCoordinate;
+use crate::game::{Game, GameError};
+use crate::notation::{self, NotationError};
+use crate::piece::{Bug, Color, EXPANSIONS};
+use crate::rules::RuleSet;
+
+// Verbs that don't change the board: starting, confirming, picking up pieces and offering draws
+const IGNORED_VERBS: [&str; 9] = [
+    "start",
+    "done",
+    "pick",
+    "pickb",
+    "resign",
+    "offerdraw",
+    "acceptdraw",
+    "declinedraw",
+    "edit",
+];
+
+#[derive(Debug, PartialEq)]
+pub enum ImportError {
+    UnknownVariant(String),
+    // Moves are identified by the number the record gives them
+    Unreadable {
+        number: usize,
+        text: String,
+    },
+    Illegal {
+        number: usize,
+        text: String,
+        error: GameError,
+    },
+    // The cell and the piece the move is written next to are in different places
+    Misplaced {
+        number: usize,
+        text: String,
+    },
+    // The piece to move has another one on top of it
+    Covered {
+        number: usize,
+        text: String,
+    },
+}
+
+impl std::fmt::Display for ImportError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ImportError::UnknownVariant(variant) => write!(f, "Unknown variant {variant}"),
+            ImportError::Unreadable { number, text } => {
+                write!(f, "Move {number} can't be read: {text}")
+            }
+            ImportError::Illegal {
+                number,
+                text,
+                error,
+            } => write!(f, "Move {number} ({text}): {error}"),
+            ImportError::Misplaced { number, text } => {
+                write!(f, "Move {number} ({text}) isn't next to the piece it names")
+            }
+            ImportError::Covered { number, text } => {
+                write!(f, "Move {number} ({text}) moves a piece that is covered")
+            }
+        }
+    }
+}
+
+// Replays a record through the rules, failing at the first move the game doesn't allow
+pub fn import(record: &str) -> Result<Game, ImportError> {
+    let variant = properties(record, "SU").next().unwrap_or("hive");
+    let mut game = Game::new(RuleSet {
+        expansions: expansions(variant)?,
+        first_player: Color::White,
+        // Boardspace doesn't end games on repetitions
+        threefold_repetition: false,
+        ..Default::default()
+    });
+
+    // The first piece is always placed at the origin, the rest of the board follows it
+    let mut origin = None;
+    // History entries already matched with the record, the rest are passes the game made on its own
+    let mut replayed = 0;
+
+    let moves = properties(record, "P0").chain(properties(record, "P1"));
+    let mut moves: Vec<(usize, &str)> = moves
+        .filter_map(|text| {
+            let (number, rest) = text.trim().split_once(' ')?;
+            Some((number.parse().ok()?, rest))
+        })
+        .collect();
+    moves.sort_by_key(|&(number, _)| number);
+
+    for (number, text) in moves {
+        let unreadable = || ImportError::Unreadable {
+            number,
+            text: text.to_string(),
+        };
+        let illegal = |error| ImportError::Illegal {
+            number,
+            text: text.to_string(),
+            error,
+        };
+
+        let words: Vec<&str> = text.split_whitespace().collect();
+        let (piece, column, row, position) = match words.as_slice() {
+            [verb, ..] if IGNORED_VERBS.contains(&verb.to_lowercase().as_str()) => continue,
+            ["pass"] | ["Pass"] => {
+                if game.history().len() == replayed {
+                    return Err(illegal(GameError::InvalidMove));
+                }
+                replayed += 1;
+                continue;
+            }
+            ["dropb" | "pdropb", piece, column, row, rest @ ..] => {
+                (piece, column, row, rest.first())
+            }
+            ["move" | "pmove", _, piece, column, row, rest @ ..] => {
+                (piece, column, row, rest.first())
+            }
+            _ => return Err(unreadable()),
+        };
+
+        let piece = notation::parse_piece(piece).map_err(|_| unreadable())?;
+        let cell = cell(column, row).ok_or_else(unreadable)?;
+        let origin = *origin.get_or_insert(cell);
+        let to = XYCoordinate {
+            x: cell.x - origin.x,
+            y: origin.y - cell.y,
+        };
+
+        // "." is the first piece, which has nothing to be next to
+        if let Some(&position) = position.filter(|&&position| position != ".") {
+            let located = notation::locate(&game, position).map_err(|_| unreadable())?;
+            if located != to {
+                return Err(ImportError::Misplaced {
+                    number,
+                    text: text.to_string(),
+                });
+            }
+        }
+
+        let action = notation::action(&mut game, piece, to).map_err(|error| match error {
+            NotationError::Illegal(error) => illegal(error),
+            NotationError::PieceCovered(_) => ImportError::Covered {
+                number,
+                text: text.to_string(),
+            },
+            _ => unreadable(),
+        })?;
+
+        // Passes the record skipped are still made by the game
+        replayed = game.history().len() + 1;
+        game.play(action).map_err(illegal)?;
+    }
+
+    Ok(game)
+}
+
+// The expansions of a variant like "hive-lmp"
+fn expansions(variant: &str) -> Result<Vec<Bug>, ImportError> {
+    let unknown = || ImportError::UnknownVariant(variant.to_string());
+
+    let letters = match variant.to_lowercase().split_once('-') {
+        _ if variant.eq_ignore_ascii_case("hive") => String::new(),
+        Some(("hive", letters)) => letters.to_string(),
+        _ => return Err(unknown()),
+    };
+
+    let mut expansions = vec![];
+    for letter in letters.chars() {
+        let bug = EXPANSIONS
+            .iter()
+            .find(|bug| notation::bug_letter(bug).to_ascii_lowercase() == letter)
+            .ok_or_else(unknown)?;
+        if !expansions.contains(bug) {
+            expansions.push(bug.clone());
+        }
+    }
+    Ok(expansions)
+}
+
+// A column letter and a row number, e.g. "N" and "13", in the same axes as XYCoordinate
+fn cell(column: &str, row: &str) -> Option<XYCoordinate> {
+    let [column] = column.as_bytes() else {
+        return None;
+    };
+    if !column.is_ascii_uppercase() {
+        return None;
+    }
+
+    Some(XYCoordinate {
+        x: (column - b'A') as i8,
+        y: row.parse().ok()?,
+    })
+}
+
+// The values of every property with the given name, e.g. "hive-m" for "SU[hive-m]"
+fn properties<'a>(record: &'a str, name: &'a str) -> impl Iterator<Item = &'a str> + 'a {
+    record.match_indices('[').filter_map(move |(start, _)| {
+        let before = &record[..start];
+        let property = &before[before
+            .trim_end_matches(|c: char| c.is_ascii_alphanumeric())
+            .len()..];
+        if property != name {
+            return None;
+        }
+
+        let value = &record[start + 1..];
+        Some(&value[..value.find(']')?])
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::Action;
+    use crate::piece::Piece;
+
+    // Written by hand in the format of boardspace.net records, the opening of a game with the pillbug
+    const RECORD: &str = r#"(;
+GM[27]VV[1]
+SU[hive-p]
+P0[id "alice"]
+P1[id "bob"]
+; P0[0 Start P0]
+; P0[1 dropb wQ N 13 .]
+; P0[2 done]
+; P1[3 dropb bQ O 13 wQ-]
+; P1[4 done]
+; P0[5 pick W 3 wA1]
+; P0[6 dropb wA1 M 13 -wQ]
+; P0[7 done]
+; P1[8 dropb bP P 13 bQ-]
+; P1[9 done]
+; P0[10 move W wA1 P 12 bP/]
+; P0[11 done]
+; P1[12 resign]
+)"#;
+
+    #[test]
+    fn imports_records() {
+        let game = import(RECORD).unwrap();
+
+        assert_eq!(game.history().len(), 5);
+        assert_eq!(
+            game.history().last(),
+            Some(&Action::Move {
+                piece: Piece {
+                    bug: Bug::Ant,
+                    color: Color::White,
+                    number: 1,
+                },
+                from: (-1, 0).into(),
+                to: (2, 1).into(),
+            })
+        );
+        assert!(game.get_pool().iter().any(|p| p.bug == Bug::Pillbug)); // the white pillbug
+    }
+
+    #[test]
+    fn flags_illegal_records() {
+        let record = RECORD.replace("dropb bP P 13 bQ-", "dropb bA1 M 14 /wA1");
+
+        assert_eq!(
+            import(&record).err(),
+            Some(ImportError::Illegal {
+                number: 8,
+                text: "dropb bA1 M 14 /wA1".to_string(),
+                error: GameError::SpawnedInOpponentsHive,
+            })
+        );
+        assert_eq!(
+            import(&RECORD.replace("M 13 -wQ", "M 13 wQ-")).err(),
+            Some(ImportError::Misplaced {
+                number: 6,
+                text: "dropb wA1 M 13 wQ-".to_string(),
+            })
+        ); // the cell is left of the white bee, but the notation says right
+        assert_eq!(
+            import("SU[hive-x]").err(),
+            Some(ImportError::UnknownVariant("hive-x".to_string()))
+        );
+
+        // The black beetle climbs on its bee, which can't move from under it
+        let record = RECORD
+            .replace("dropb bP P 13 bQ-", "dropb bB1 P 13 bQ-")
+            .replace("move W wA1 P 12 bP/", "move W wA1 O 12 bQ/")
+            .replace(
+                "P1[12 resign]",
+                "P1[12 move B bB1 O 13 bQ]; P0[13 move W wA1 M 13 -wQ]; P1[14 move B bQ O 14 /bB1]",
+            );
+        assert_eq!(
+            import(&record).err(),
+            Some(ImportError::Covered {
+                number: 14,
+                text: "move B bQ O 14 /bB1".to_string(),
+            })
+        );
+        assert!(matches!(
+            import("; P0[1 dropb wQ 13 N .]"),
+            Err(ImportError::Unreadable { number: 1, .. })
+        ));
+    }
+}
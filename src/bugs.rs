@@ -0,0 +1,622 @@
+use std::{
+    collections::{HashMap, HashSet},
+    rc::Rc,
+};
+
+use crate::coordinate::{HexagonalCoordinateSystem, XYCoordinate};
+use crate::game::{Board, GameError};
+use crate::notation;
+use crate::piece::{Bug, BugTrait, BUILT_IN};
+
+// Behaviour of every bug that can be played, looked up by the game to move pieces
+// New bugs can be added by registering them as a Bug::Custom and adding them to the RuleSet's expansions
+#[derive(Clone)]
+pub struct BugRegistry {
+    behaviours: HashMap<Bug, Rc<dyn BugTrait>>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum RegistryError {
+    // Homebrew bugs are written with a capital letter, which must not be one of the built-in bugs'
+    LetterTaken(char),
+    NotACapitalLetter(char),
+}
+
+impl std::fmt::Display for RegistryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RegistryError::LetterTaken(letter) => {
+                write!(f, "The letter {letter} is taken by a built-in bug")
+            }
+            RegistryError::NotACapitalLetter(letter) => {
+                write!(f, "Homebrew bugs go by a capital letter, not {letter}")
+            }
+        }
+    }
+}
+
+impl BugRegistry {
+    // Built-in bugs can be given other moves, homebrew ones need a letter of their own
+    pub fn register(
+        &mut self,
+        bug: Bug,
+        behaviour: impl BugTrait + 'static,
+    ) -> Result<(), RegistryError> {
+        if let Bug::Custom(letter) = bug {
+            if !letter.is_ascii_uppercase() {
+                return Err(RegistryError::NotACapitalLetter(letter));
+            }
+
+            if BUILT_IN
+                .iter()
+                .any(|bug| notation::bug_letter(bug) == letter)
+            {
+                return Err(RegistryError::LetterTaken(letter));
+            }
+        }
+
+        self.behaviours.insert(bug, Rc::new(behaviour));
+        Ok(())
+    }
+
+    pub fn knows(&self, bug: &Bug) -> bool {
+        self.behaviours.contains_key(bug)
+    }
+
+    // Moves of the top piece at the given position as if it was the given bug
+    pub fn possible_moves(
+        &self,
+        bug: &Bug,
+        board: &mut Board,
+        from: XYCoordinate,
+    ) -> Result<HashSet<XYCoordinate>, GameError> {
+        self.behaviours
+            .get(bug)
+            .ok_or_else(|| GameError::UnregisteredBug(bug.clone()))?
+            .possible_moves(board, from, self)
+    }
+}
+
+impl Default for BugRegistry {
+    fn default() -> Self {
+        let behaviours: [(Bug, Rc<dyn BugTrait>); 8] = [
+            (Bug::Bee, Rc::new(Bee)),
+            (Bug::Beetle, Rc::new(Beetle)),
+            (Bug::Grasshopper, Rc::new(Grasshopper)),
+            (Bug::Spider, Rc::new(Spider)),
+            (Bug::Ant, Rc::new(Ant)),
+            (Bug::Mosquito, Rc::new(Mosquito)),
+            (Bug::Ladybug, Rc::new(Ladybug)),
+            (Bug::Pillbug, Rc::new(Pillbug)),
+        ];
+
+        BugRegistry {
+            behaviours: HashMap::from(behaviours),
+        }
+    }
+}
+
+// Behaviours can't be compared, so registries are equal if they know the same bugs
+impl PartialEq for BugRegistry {
+    fn eq(&self, other: &Self) -> bool {
+        self.behaviours.len() == other.behaviours.len()
+            && self
+                .behaviours
+                .keys()
+                .all(|bug| other.behaviours.contains_key(bug))
+    }
+}
+
+pub struct Bee;
+
+impl BugTrait for Bee {
+    fn possible_moves(
+        &self,
+        board: &mut Board,
+        from: XYCoordinate,
+        _: &BugRegistry,
+    ) -> Result<HashSet<XYCoordinate>, GameError> {
+        let hive = board.hive_without(from);
+
+        Ok(board.crawl_steps(from, &hive))
+    }
+}
+
+pub struct Beetle;
+
+impl BugTrait for Beetle {
+    fn possible_moves(
+        &self,
+        board: &mut Board,
+        from: XYCoordinate,
+        _: &BugRegistry,
+    ) -> Result<HashSet<XYCoordinate>, GameError> {
+        board
+            .lifting(from, |board| {
+                let crawl_steps = board.crawl_steps(from, &board.hive());
+
+                board
+                    .coordinate_system
+                    .neighbor_coordinates(from)
+                    .into_iter()
+                    .filter(|&to| {
+                        if board.height(from) == 0 && board.height(to) == 0 {
+                            // On the ground it crawls like any other bug
+                            crawl_steps.contains(&to)
+                        } else {
+                            // Climbing, walking on top of the hive and going down all go through gates
+                            board.can_step(from, to).unwrap_or(false)
+                        }
+                    })
+                    .collect()
+            })
+            .ok_or(GameError::NoPieceAtLocation)
+    }
+}
+
+pub struct Grasshopper;
+
+impl BugTrait for Grasshopper {
+    fn possible_moves(
+        &self,
+        board: &mut Board,
+        from: XYCoordinate,
+        _: &BugRegistry,
+    ) -> Result<HashSet<XYCoordinate>, GameError> {
+        let hive = board.hive_without(from);
+
+        let possible_destinies = board
+            .coordinate_system
+            .relative_neighbors_clockwise()
+            .into_iter()
+            .flat_map(|direction| {
+                let position = from + direction;
+
+                if !hive.contains(&position) {
+                    return None;
+                }
+
+                let mut last = position;
+                while hive.contains(&last) {
+                    last = last + direction;
+                }
+                Some(last)
+            });
+
+        Ok(possible_destinies.collect())
+    }
+}
+
+pub struct Spider;
+
+impl BugTrait for Spider {
+    fn possible_moves(
+        &self,
+        board: &mut Board,
+        from: XYCoordinate,
+        _: &BugRegistry,
+    ) -> Result<HashSet<XYCoordinate>, GameError> {
+        let hive = board.hive_without(from);
+
+        let mut paths = vec![vec![from]];
+
+        for _ in 0..3 {
+            let mut new_paths = vec![];
+
+            for path in paths {
+                let last = *path.last().expect("every path starts where the spider is");
+
+                for neighbor in board.crawl_steps(last, &hive) {
+                    if !path.contains(&neighbor) {
+                        let mut new_path = path.clone();
+                        new_path.push(neighbor);
+                        new_paths.push(new_path);
+                    }
+                }
+            }
+
+            paths = new_paths;
+        }
+
+        Ok(paths.iter().flat_map(|p| p.last()).cloned().collect())
+    }
+}
+
+pub struct Ant;
+
+impl BugTrait for Ant {
+    fn possible_moves(
+        &self,
+        board: &mut Board,
+        from: XYCoordinate,
+        _: &BugRegistry,
+    ) -> Result<HashSet<XYCoordinate>, GameError> {
+        let hive = board.hive_without(from);
+
+        // Traverse the tree
+        let mut reachable: HashSet<XYCoordinate> = HashSet::new();
+        let mut to_check = vec![from];
+
+        while let Some(current) = to_check.pop() {
+            for neighbor in board.crawl_steps(current, &hive) {
+                if neighbor != from && reachable.insert(neighbor) {
+                    to_check.push(neighbor);
+                }
+            }
+        }
+
+        Ok(reachable)
+    }
+}
+
+pub struct Mosquito;
+
+impl BugTrait for Mosquito {
+    fn possible_moves(
+        &self,
+        board: &mut Board,
+        from: XYCoordinate,
+        bugs: &BugRegistry,
+    ) -> Result<HashSet<XYCoordinate>, GameError> {
+        // On top of the hive the mosquito can only move like a beetle
+        if board
+            .get_cell(from)
+            .ok_or(GameError::NoPieceAtLocation)?
+            .len()
+            > 1
+        {
+            return bugs.possible_moves(&Bug::Beetle, board, from);
+        }
+
+        let mut touched: Vec<Bug> = vec![];
+        for piece in board.neighbor_pieces(from) {
+            if piece.bug != Bug::Mosquito && !touched.contains(&piece.bug) {
+                touched.push(piece.bug.clone());
+            }
+        }
+
+        let mut moves = HashSet::new();
+        for bug in touched {
+            moves.extend(bugs.possible_moves(&bug, board, from)?);
+        }
+        Ok(moves)
+    }
+}
+
+pub struct Ladybug;
+
+impl BugTrait for Ladybug {
+    fn possible_moves(
+        &self,
+        board: &mut Board,
+        from: XYCoordinate,
+        _: &BugRegistry,
+    ) -> Result<HashSet<XYCoordinate>, GameError> {
+        // The ladybug must not be part of the hive it walks on
+        board
+            .lifting(from, |board| Ladybug::walk(board, from))
+            .ok_or(GameError::NoPieceAtLocation)
+    }
+}
+
+impl Ladybug {
+    // Two steps on top of the hive and then one down, expects the ladybug to be off the board
+    fn walk(board: &Board, from: XYCoordinate) -> HashSet<XYCoordinate> {
+        let coordinate_system = &board.coordinate_system;
+        let mut moves = HashSet::new();
+
+        for first in coordinate_system.neighbor_coordinates(from) {
+            if board.height(first) == 0 || !board.can_step(from, first).unwrap_or(false) {
+                continue;
+            }
+
+            for second in coordinate_system.neighbor_coordinates(first) {
+                if board.height(second) == 0 || !board.can_step(first, second).unwrap_or(false) {
+                    continue;
+                }
+
+                for third in coordinate_system.neighbor_coordinates(second) {
+                    if third != from
+                        && board.height(third) == 0
+                        && board.can_step(second, third).unwrap_or(false)
+                    {
+                        moves.insert(third);
+                    }
+                }
+            }
+        }
+
+        moves
+    }
+}
+
+// Its ability to throw other pieces is an action of its own, see Game::throw
+pub struct Pillbug;
+
+impl BugTrait for Pillbug {
+    fn possible_moves(
+        &self,
+        board: &mut Board,
+        from: XYCoordinate,
+        bugs: &BugRegistry,
+    ) -> Result<HashSet<XYCoordinate>, GameError> {
+        Bee.possible_moves(board, from, bugs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coordinate::{GenericCoordinateSystem, RELATIVE_NEIGHBORS_CLOCKWISE};
+    use crate::game::{Action, Game};
+    use crate::notation;
+    use crate::piece::{Color, Piece};
+    use crate::rules::RuleSet;
+
+    // A homebrew bug that teleports to the opposite side of the origin
+    struct Mirror;
+
+    impl BugTrait for Mirror {
+        fn possible_moves(
+            &self,
+            _: &mut Board,
+            from: XYCoordinate,
+            _: &BugRegistry,
+        ) -> Result<HashSet<XYCoordinate>, GameError> {
+            Ok(HashSet::from([(-from.x, -from.y).into()]))
+        }
+    }
+
+    // A homebrew bug that flies to any free cell around the rest of the hive
+    struct Dragonfly;
+
+    impl BugTrait for Dragonfly {
+        fn possible_moves(
+            &self,
+            board: &mut Board,
+            from: XYCoordinate,
+            _: &BugRegistry,
+        ) -> Result<HashSet<XYCoordinate>, GameError> {
+            let hive = board.hive_without(from);
+
+            Ok(hive
+                .iter()
+                .flat_map(|&c| board.coordinate_system.neighbor_coordinates(c))
+                .filter(|c| c != &from && !hive.contains(c))
+                .collect())
+        }
+    }
+
+    #[test]
+    fn beetle_respects_gates_at_every_height() {
+        let black_beetle = Piece {
+            bug: Bug::Beetle,
+            color: Color::Black,
+            number: 1,
+        };
+        let white_ant = Piece {
+            bug: Bug::Ant,
+            color: Color::White,
+            number: 1,
+        };
+
+        let mut board = Board::new(GenericCoordinateSystem::new(RELATIVE_NEIGHBORS_CLOCKWISE));
+        board.put_piece(black_beetle.clone(), (0, 0).into());
+        board.put_piece(white_ant.clone(), (0, 1).into());
+        board.put_piece(white_ant.clone(), (1, -1).into());
+
+        let moves = Beetle
+            .possible_moves(&mut board, (0, 0).into(), &BugRegistry::default())
+            .unwrap();
+        assert!(!moves.contains(&(1, 0).into())); // sliding on the ground between (0, 1) and (1, -1)
+        assert!(moves.contains(&(0, 1).into())); // climbing is fine
+
+        board.put_piece(white_ant.clone(), (1, 0).into());
+
+        let moves = Beetle
+            .possible_moves(&mut board, (0, 0).into(), &BugRegistry::default())
+            .unwrap();
+        assert!(moves.contains(&(1, 0).into())); // climbing onto a stack as tall as the gate
+
+        board.put_piece(black_beetle.clone(), (0, 1).into());
+        board.put_piece(black_beetle.clone(), (1, -1).into());
+
+        let moves = Beetle
+            .possible_moves(&mut board, (0, 0).into(), &BugRegistry::default())
+            .unwrap();
+        assert!(!moves.contains(&(1, 0).into())); // both sides of the gate are taller now
+
+        board.put_piece(black_beetle.clone(), (0, 0).into());
+        board.put_piece(black_beetle.clone(), (0, 0).into());
+
+        let moves = Beetle
+            .possible_moves(&mut board, (0, 0).into(), &BugRegistry::default())
+            .unwrap();
+        assert!(moves.contains(&(1, 0).into())); // from high enough the gate doesn't matter
+        assert_eq!(board.height((0, 0).into()), 3);
+    }
+
+    #[test]
+    fn crawling_bugs_keep_in_touch_with_the_hive() {
+        let black_bee = Piece {
+            bug: Bug::Bee,
+            color: Color::Black,
+            number: 1,
+        };
+        let white_ant = Piece {
+            bug: Bug::Ant,
+            color: Color::White,
+            number: 1,
+        };
+
+        let mut board = Board::new(GenericCoordinateSystem::new(RELATIVE_NEIGHBORS_CLOCKWISE));
+        board.put_piece(white_ant.clone(), (0, 0).into());
+        board.put_piece(black_bee.clone(), (1, 0).into());
+        board.put_piece(black_bee.clone(), (2, 0).into());
+
+        let bugs = BugRegistry::default();
+
+        assert_eq!(
+            Bee.possible_moves(&mut board, (0, 0).into(), &bugs),
+            Ok(HashSet::from([(0, 1).into(), (1, -1).into()]))
+        );
+        assert_eq!(
+            Spider.possible_moves(&mut board, (0, 0).into(), &bugs),
+            Ok(HashSet::from([(2, 1).into(), (3, -1).into()]))
+        ); // three steps around the line, one way or the other
+        assert_eq!(
+            Ant.possible_moves(&mut board, (0, 0).into(), &bugs),
+            Ok(HashSet::from([
+                (0, 1).into(),
+                (1, 1).into(),
+                (2, 1).into(),
+                (3, 0).into(),
+                (3, -1).into(),
+                (2, -1).into(),
+                (1, -1).into(),
+            ]))
+        ); // anywhere around the line but where it started
+    }
+
+    #[test]
+    fn crawling_bugs_do_not_go_through_gates() {
+        let black_bee = Piece {
+            bug: Bug::Bee,
+            color: Color::Black,
+            number: 1,
+        };
+        let white_ant = Piece {
+            bug: Bug::Ant,
+            color: Color::White,
+            number: 1,
+        };
+
+        // (0, 0) is surrounded but for (0, -1), which is a gate between (-1, 0) and (1, -1)
+        let mut board = Board::new(GenericCoordinateSystem::new(RELATIVE_NEIGHBORS_CLOCKWISE));
+        for coordinate in [(-1, 0), (-1, 1), (0, 1), (1, 0), (1, -1)] {
+            board.put_piece(black_bee.clone(), coordinate.into());
+        }
+        board.put_piece(white_ant, (-1, -1).into());
+
+        let moves = Ant
+            .possible_moves(&mut board, (-1, -1).into(), &BugRegistry::default())
+            .unwrap();
+
+        assert!(moves.contains(&(0, -1).into()));
+        assert!(!moves.contains(&(0, 0).into()));
+    }
+
+    #[test]
+    fn game_moves_pieces_with_registered_bugs() {
+        let black_bee = Piece {
+            bug: Bug::Bee,
+            color: Color::Black,
+            number: 1,
+        };
+        let white_bee = Piece {
+            bug: Bug::Bee,
+            color: Color::White,
+            number: 1,
+        };
+        let black_ant = Piece {
+            bug: Bug::Ant,
+            color: Color::Black,
+            number: 1,
+        };
+
+        let mut bugs = BugRegistry::default();
+        bugs.register(Bug::Ant, Mirror).unwrap();
+
+        let mut game = Game::with_bugs(RuleSet::default(), bugs);
+
+        game.put(black_bee, (0, 0).into()).unwrap();
+        game.put(white_bee, (1, 0).into()).unwrap();
+        game.put(black_ant, (-1, 0).into()).unwrap();
+
+        assert_eq!(
+            game.possible_moves((-1, 0).into()),
+            Ok(HashSet::from([(1, 0).into()]))
+        );
+        assert_eq!(game.possible_moves((1, 0).into()).unwrap().len(), 2); // bees keep their moves
+    }
+
+    #[test]
+    fn game_plays_homebrew_bugs() {
+        let dragonfly = Bug::Custom('D');
+
+        let mut bugs = BugRegistry::default();
+        bugs.register(dragonfly.clone(), Dragonfly).unwrap();
+
+        let mut game = Game::with_bugs(
+            RuleSet {
+                expansions: vec![dragonfly.clone()],
+                ..Default::default()
+            },
+            bugs,
+        );
+
+        for text in ["bQ", "wQ bQ-", "bD -bQ", "wA1 wQ-"] {
+            let action = notation::parse(&mut game, text).unwrap();
+            game.play(action).unwrap();
+        }
+
+        let black_dragonfly = Piece {
+            bug: dragonfly,
+            color: Color::Black,
+            number: 1,
+        };
+        assert_eq!(game.position(&black_dragonfly), Some((-1, 0).into()));
+        assert_eq!(black_dragonfly.to_string(), "⚫ D");
+        assert!(game
+            .possible_moves((-1, 0).into())
+            .unwrap()
+            .contains(&(3, 0).into())); // anywhere around the hive
+
+        let action = notation::parse(&mut game, "bD wA1-").unwrap();
+        game.play(action).unwrap();
+        assert_eq!(
+            notation::history(&game).last(),
+            Some(&"bD wA1-".to_string())
+        );
+    }
+
+    #[test]
+    fn homebrew_bugs_need_a_letter_of_their_own() {
+        let mut bugs = BugRegistry::default();
+
+        assert_eq!(
+            bugs.register(Bug::Custom('Q'), Dragonfly),
+            Err(RegistryError::LetterTaken('Q'))
+        ); // it would be read back as the bee
+        assert_eq!(
+            bugs.register(Bug::Custom('d'), Dragonfly),
+            Err(RegistryError::NotACapitalLetter('d'))
+        );
+        assert!(!bugs.knows(&Bug::Custom('Q')));
+        assert_eq!(bugs.register(Bug::Custom('D'), Dragonfly), Ok(()));
+    }
+
+    #[test]
+    fn game_refuses_unregistered_bugs() {
+        let dragonfly = Bug::Custom('D');
+        let black_dragonfly = Piece {
+            bug: dragonfly.clone(),
+            color: Color::Black,
+            number: 1,
+        };
+
+        let mut game = Game::new(RuleSet {
+            expansions: vec![dragonfly.clone()],
+            ..Default::default()
+        });
+
+        assert_eq!(
+            game.put(black_dragonfly.clone(), (0, 0).into()),
+            Err(GameError::UnregisteredBug(dragonfly))
+        );
+        assert!(!game.legal_actions().iter().any(|action| matches!(
+            action,
+            Action::Put { piece, .. } if piece == &black_dragonfly
+        ))); // it's never offered either
+    }
+}
@@ -1,10 +1,13 @@
 use std::{collections::HashSet, error::Error, marker::PhantomData};
 
+use crate::board::Zobrist;
+use crate::rng::splitmix;
+
 // Useful guide for understanding hexagonal coordinates: https://www.redblobgames.com/grids/hexagons/#neighbors-axial
 
 // [(-1, 0), (-1, 1), (0, 1), (1, 0), (1, -1), (0, -1)]
 // starts from the left and goes clockwise
-pub(crate) const RELATIVE_NEIGHBORS_CLOCKWISE: [XYCoordinate; 6] = [
+pub const RELATIVE_NEIGHBORS_CLOCKWISE: [XYCoordinate; 6] = [
     XYCoordinate { x: -1, y: 0 },
     XYCoordinate { x: -1, y: 1 },
     XYCoordinate { x: 0, y: 1 },
@@ -13,10 +16,11 @@ pub(crate) const RELATIVE_NEIGHBORS_CLOCKWISE: [XYCoordinate; 6] = [
     XYCoordinate { x: 0, y: -1 },
 ];
 
-#[derive(Eq, Hash, PartialEq, Clone, Copy)]
-pub(crate) struct XYCoordinate {
-    pub(crate) x: i8,
-    pub(crate) y: i8,
+#[derive(Eq, Hash, PartialEq, Clone, Copy, Debug, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct XYCoordinate {
+    pub x: i8,
+    pub y: i8,
 }
 
 impl From<(i8, i8)> for XYCoordinate {
@@ -25,6 +29,42 @@ impl From<(i8, i8)> for XYCoordinate {
     }
 }
 
+impl Zobrist for XYCoordinate {
+    fn zobrist_id(&self) -> u64 {
+        splitmix(&[self.x as u64, self.y as u64])
+    }
+}
+
+// Each of the 6 rotations, plain and reflected, maps the grid onto itself
+pub const SYMMETRIES: usize = 12;
+
+impl XYCoordinate {
+    // Turned around the origin by the given sixths of a turn clockwise
+    // Each direction of RELATIVE_NEIGHBORS_CLOCKWISE becomes the next one
+    pub fn rotated(self, steps: usize) -> Self {
+        (0..steps % 6).fold(self, |XYCoordinate { x, y }, _| XYCoordinate {
+            x: x + y,
+            y: -x,
+        })
+    }
+
+    // Mirrored so that the direction at index i of RELATIVE_NEIGHBORS_CLOCKWISE becomes the one at 5 - i
+    pub fn reflected(self) -> Self {
+        XYCoordinate {
+            x: self.y,
+            y: self.x,
+        }
+    }
+
+    // One of the SYMMETRIES: the first 6 are rotations and the rest rotate the reflection
+    pub fn symmetric(self, symmetry: usize) -> Self {
+        match symmetry < 6 {
+            true => self.rotated(symmetry),
+            false => self.reflected().rotated(symmetry),
+        }
+    }
+}
+
 impl std::ops::Add for XYCoordinate {
     type Output = Self;
 
@@ -47,7 +87,7 @@ impl std::ops::Sub for XYCoordinate {
     }
 }
 
-pub(crate) trait HexagonalCoordinateSystem {
+pub trait HexagonalCoordinateSystem {
     type Coordinate;
     type Direction;
 
@@ -66,13 +106,14 @@ pub(crate) trait HexagonalCoordinateSystem {
 }
 
 #[derive(PartialEq, Clone)]
-pub(crate) struct GenericCoordinateSystem<Coordinate, Direction> {
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GenericCoordinateSystem<Coordinate, Direction> {
     neighbors: [Direction; 6],
     _phantom: PhantomData<Coordinate>,
 }
 
 impl<Coordinate, Direction> GenericCoordinateSystem<Coordinate, Direction> {
-    pub(crate) fn new(neighbors: [Direction; 6]) -> Self {
+    pub fn new(neighbors: [Direction; 6]) -> Self {
         Self {
             neighbors,
             _phantom: PhantomData,
@@ -128,3 +169,27 @@ where
         self.neighbors
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn symmetries_keep_the_neighbors_around() {
+        for (i, &direction) in RELATIVE_NEIGHBORS_CLOCKWISE.iter().enumerate() {
+            assert_eq!(
+                direction.rotated(1),
+                RELATIVE_NEIGHBORS_CLOCKWISE[(i + 1) % 6]
+            );
+            assert_eq!(direction.reflected(), RELATIVE_NEIGHBORS_CLOCKWISE[5 - i]);
+        }
+
+        let coordinate = XYCoordinate { x: 3, y: -1 };
+        assert_eq!(coordinate.rotated(6), coordinate); // a whole turn
+        assert_eq!(coordinate.symmetric(0), coordinate);
+
+        let images: HashSet<XYCoordinate> =
+            (0..SYMMETRIES).map(|s| coordinate.symmetric(s)).collect();
+        assert_eq!(images.len(), SYMMETRIES); // not on any mirror axis, so every image is different
+    }
+}
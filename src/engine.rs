@@ -0,0 +1,227 @@
+// Computer players: they look at a game and choose what to play in it
+// Searches look ahead by making actions and unmaking them on the same game instead of cloning it
+
+use std::time::{Duration, Instant};
+
+use crate::game::{Action, Game, GameResult};
+
+// Score of a won game, far above anything an evaluator returns
+// Wins found sooner score higher, so the quickest one is chosen
+pub const WIN: i32 = 1_000_000;
+
+pub trait Player {
+    // The action to play, or None if the game is finished
+    // The game has to be left as it was given
+    fn choose(&mut self, game: &mut Game) -> Option<Action>;
+}
+
+// How good the position is for the player to move, positive when they are ahead
+pub trait Evaluator {
+    fn evaluate(&self, game: &mut Game) -> i32;
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Limit {
+    // Actions ahead, counting both players' and the passes the game makes on its own
+    Depth(u8),
+    // The deepest search finished in time wins, at least one action ahead is always searched
+    Time(Duration),
+}
+
+// Pieces around the opponent's bee minus pieces around one's own
+#[derive(Default)]
+pub struct QueenPressure;
+
+impl Evaluator for QueenPressure {
+    fn evaluate(&self, game: &mut Game) -> i32 {
+        let own = game.around_bee(game.turn()) as i32;
+        let opponent = game.around_bee(&!game.turn().clone()) as i32;
+        opponent - own
+    }
+}
+
+// Negamax with alpha-beta pruning, searching one action deeper each time until it hits the limit
+pub struct AlphaBeta<E> {
+    pub evaluator: E,
+    pub limit: Limit,
+}
+
+impl<E: Evaluator> AlphaBeta<E> {
+    pub fn new(evaluator: E, limit: Limit) -> Self {
+        AlphaBeta { evaluator, limit }
+    }
+
+    // The best action at the given depth and its score, or None if the time ran out first
+    fn search_root(
+        &self,
+        game: &mut Game,
+        actions: &[Action],
+        depth: u8,
+        deadline: Option<Instant>,
+    ) -> Option<(Action, i32)> {
+        let mut best: Option<(Action, i32)> = None;
+        let mut alpha = -WIN - 1;
+
+        for action in actions {
+            let score = self.score_action(game, action, depth, alpha, WIN + 1, 0, deadline)?;
+
+            if best.as_ref().is_none_or(|(_, best)| score > *best) {
+                best = Some((action.clone(), score));
+            }
+            alpha = alpha.max(score);
+        }
+        best
+    }
+
+    // Score of the position after the action, for the player making it
+    #[allow(clippy::too_many_arguments)]
+    fn score_action(
+        &self,
+        game: &mut Game,
+        action: &Action,
+        depth: u8,
+        alpha: i32,
+        beta: i32,
+        ply: i32,
+        deadline: Option<Instant>,
+    ) -> Option<i32> {
+        let mover = game.turn().clone();
+        game.make(action.clone())
+            .expect("legal actions can always be made");
+
+        // After a pass the same player moves again, so the score isn't negated
+        let score = match game.turn() == &mover {
+            true => self.negamax(game, depth - 1, alpha, beta, ply + 1, deadline),
+            false => self
+                .negamax(game, depth - 1, -beta, -alpha, ply + 1, deadline)
+                .map(|score| -score),
+        };
+
+        game.unmake().expect("the action was just made");
+        score
+    }
+
+    // Score of the position for the player to move, or None if the time ran out
+    fn negamax(
+        &self,
+        game: &mut Game,
+        depth: u8,
+        mut alpha: i32,
+        beta: i32,
+        ply: i32,
+        deadline: Option<Instant>,
+    ) -> Option<i32> {
+        if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+            return None;
+        }
+
+        match game.result() {
+            Some(GameResult::Draw(_)) => return Some(0),
+            Some(GameResult::Win(color)) if color == game.turn() => return Some(WIN - ply),
+            Some(GameResult::Win(_)) => return Some(ply - WIN),
+            None => {}
+        }
+
+        let actions = game.legal_actions();
+        if depth == 0 || actions.is_empty() {
+            return Some(self.evaluator.evaluate(game));
+        }
+
+        let mut best = -WIN - 1;
+        for action in &actions {
+            let score = self.score_action(game, action, depth, alpha, beta, ply, deadline)?;
+
+            best = best.max(score);
+            alpha = alpha.max(score);
+            if alpha >= beta {
+                break;
+            }
+        }
+        Some(best)
+    }
+}
+
+impl<E: Evaluator> Player for AlphaBeta<E> {
+    fn choose(&mut self, game: &mut Game) -> Option<Action> {
+        let mut actions = game.legal_actions();
+        let mut best = actions.first()?.clone();
+
+        let (deadline, max_depth) = match self.limit {
+            Limit::Depth(depth) => (None, depth.max(1)),
+            Limit::Time(time) => (Some(Instant::now() + time), u8::MAX),
+        };
+
+        for depth in 1..=max_depth {
+            // The first search always finishes, so there's an action even if the time is too short
+            let deadline = deadline.filter(|_| depth > 1);
+            let Some((action, score)) = self.search_root(game, &actions, depth, deadline) else {
+                break;
+            };
+            best = action;
+
+            // The best action goes first next time, so that more of the others are cut
+            let index = actions
+                .iter()
+                .position(|a| a == &best)
+                .expect("the search picks one of the actions");
+            actions[..=index].rotate_right(1);
+
+            // Nothing else to find once the game is decided
+            if score.abs() > WIN / 2 {
+                break;
+            }
+        }
+
+        Some(best)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coordinate::XYCoordinate;
+    use crate::piece::{Bug, Color, Piece};
+    use crate::test_positions::WIN_IN_ONE;
+
+    #[test]
+    fn alpha_beta_finds_the_win() {
+        let winning = Action::Move {
+            piece: Piece {
+                bug: Bug::Ant,
+                color: Color::White,
+                number: 1,
+            },
+            from: XYCoordinate { x: -1, y: 4 },
+            to: XYCoordinate { x: 2, y: -1 },
+        };
+
+        for limit in [
+            Limit::Depth(1),
+            Limit::Depth(2),
+            Limit::Time(Duration::from_millis(50)),
+        ] {
+            let mut game = Game::from_game_string(WIN_IN_ONE).unwrap();
+            let mut player = AlphaBeta::new(QueenPressure, limit);
+
+            assert_eq!(player.choose(&mut game), Some(winning.clone()));
+            assert_eq!(game.to_game_string(), Ok(WIN_IN_ONE.to_string())); // the search leaves the game as it was
+        }
+    }
+
+    #[test]
+    fn looking_ahead_keeps_the_actions_to_redo() {
+        let mut game = Game::from_game_string(WIN_IN_ONE).unwrap();
+        game.undo().unwrap(); // black takes back its grasshopper
+        let hash = game.hash();
+
+        let mut player = AlphaBeta::new(QueenPressure, Limit::Depth(2));
+        let action = player.choose(&mut game).unwrap();
+
+        game.make(action).unwrap();
+        game.unmake().unwrap();
+        assert_eq!(game.hash(), hash);
+
+        game.redo().unwrap(); // the grasshopper can still be placed again
+        assert_eq!(game.to_game_string(), Ok(WIN_IN_ONE.to_string()));
+    }
+}
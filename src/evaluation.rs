@@ -0,0 +1,161 @@
+// Scores positions by counting what usually decides Hive games, for the engines and the UI
+
+use crate::engine::{Evaluator, WIN};
+use crate::game::{Game, GameResult};
+use crate::piece::{Bug, Color};
+
+// What one of each feature is worth to the player who has it, negative for the bad ones
+#[derive(Debug, PartialEq, Clone)]
+pub struct Weights {
+    // Taken cells around the player's own bee
+    pub around_bee: i32,
+    // Cells the player's pieces can move to
+    pub mobility: i32,
+    // The player's pieces that can't move without breaking the hive
+    pub pinned: i32,
+    // The player's pieces on top of the opponent's bee
+    pub on_enemy_bee: i32,
+    // Pieces the player still has to place
+    pub in_pool: i32,
+}
+
+impl Default for Weights {
+    fn default() -> Self {
+        Weights {
+            around_bee: -40,
+            mobility: 1,
+            pinned: -4,
+            on_enemy_bee: 30,
+            in_pool: 2,
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct Heuristic {
+    pub weights: Weights,
+}
+
+impl Heuristic {
+    pub fn new(weights: Weights) -> Self {
+        Heuristic { weights }
+    }
+
+    // The weighted features of one player
+    fn strength(&self, game: &mut Game, color: &Color) -> i32 {
+        let weights = &self.weights;
+        let pinned = game.pinned();
+
+        let mut mobility = 0;
+        let mut pinned_pieces = 0;
+        let mut on_enemy_bee = 0;
+        for coordinate in game.hive() {
+            let stack = game.stack(coordinate);
+            let Some(top) = stack.last() else {
+                continue;
+            };
+            if &top.color != color {
+                continue;
+            }
+
+            if stack.iter().any(|p| p.bug == Bug::Bee && &p.color != color) {
+                on_enemy_bee += 1;
+            }
+
+            // Pieces stacked over others are never pinned
+            if pinned.contains(&coordinate) {
+                pinned_pieces += 1;
+            } else {
+                mobility += game
+                    .possible_moves(coordinate)
+                    .map_or(0, |moves| moves.len() as i32);
+            }
+        }
+
+        let in_pool = game.get_pool().iter().filter(|p| &p.color == color).count() as i32;
+
+        weights.around_bee * game.around_bee(color) as i32
+            + weights.mobility * mobility
+            + weights.pinned * pinned_pieces
+            + weights.on_enemy_bee * on_enemy_bee
+            + weights.in_pool * in_pool
+    }
+}
+
+impl Evaluator for Heuristic {
+    // Finished games score as won, lost or even, so the UI can show them too
+    fn evaluate(&self, game: &mut Game) -> i32 {
+        let turn = game.turn().clone();
+
+        match game.result() {
+            Some(GameResult::Win(color)) if color == &turn => WIN,
+            Some(GameResult::Win(_)) => -WIN,
+            Some(GameResult::Draw(_)) => 0,
+            None => self.strength(game, &turn) - self.strength(game, &!turn.clone()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notation;
+    use crate::rules::RuleSet;
+
+    #[test]
+    fn scores_the_features_from_the_side_to_move() {
+        let mut game = Game::new(RuleSet {
+            first_player: Color::White,
+            ..Default::default()
+        });
+        for text in ["wQ", "bQ wQ-", "wB1 -wQ", "bB1 bQ-"] {
+            let action = notation::parse(&mut game, text).unwrap();
+            game.play(action).unwrap();
+        }
+
+        let none = Weights {
+            around_bee: 0,
+            mobility: 0,
+            pinned: 0,
+            on_enemy_bee: 0,
+            in_pool: 0,
+        };
+
+        assert_eq!(Heuristic::new(none.clone()).evaluate(&mut game), 0);
+        assert_eq!(
+            Heuristic::new(Weights {
+                in_pool: 1,
+                ..none.clone()
+            })
+            .evaluate(&mut game),
+            0
+        ); // both have 9 pieces left
+        assert_eq!(
+            Heuristic::new(Weights {
+                pinned: 1,
+                ..none.clone()
+            })
+            .evaluate(&mut game),
+            0
+        ); // both bees hold their beetles to the hive
+
+        // The white beetle climbs on the black bee
+        for text in ["wB1 wQ", "bB1 bQ"] {
+            let action = notation::parse(&mut game, text).unwrap();
+            game.play(action).unwrap();
+        }
+        let action = notation::parse(&mut game, "wB1 bQ").unwrap();
+        game.play(action).unwrap();
+
+        assert_eq!(game.turn(), &Color::White); // black has nothing on top to move nor to place next to, so it passes
+        assert_eq!(
+            Heuristic::new(Weights {
+                on_enemy_bee: 1,
+                ..none.clone()
+            })
+            .evaluate(&mut game),
+            1
+        ); // white sits on the black bee
+        assert!(Heuristic::default().evaluate(&mut game) > 0);
+    }
+}
@@ -1,37 +1,92 @@
 use std::collections::HashSet;
 use std::vec;
 
-use crate::board::StackableHexagonalBoard;
+use crate::board::{StackableHexagonalBoard, Zobrist};
+use crate::bugs::BugRegistry;
 use crate::coordinate::{
     GenericCoordinateSystem, HexagonalCoordinateSystem, XYCoordinate, RELATIVE_NEIGHBORS_CLOCKWISE,
 };
-use crate::piece::{Bug, Color, Piece};
+use crate::notation::{self, NotationError};
+use crate::piece::{Bug, Color, Piece, EXPANSIONS};
+use crate::rng::splitmix;
+use crate::rules::RuleSet;
+
+pub type Board = StackableHexagonalBoard<
+    Piece,
+    GenericCoordinateSystem<XYCoordinate, XYCoordinate>,
+    XYCoordinate,
+>;
 
 #[derive(PartialEq, Clone)]
-pub(crate) struct Game {
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Game {
     turn: Color,
     result: Option<GameResult>,
-    board: StackableHexagonalBoard<
-        Piece,
-        GenericCoordinateSystem<XYCoordinate, XYCoordinate>,
-        XYCoordinate,
-    >,
-    turn_number: u8,
+    board: Board,
+    turn_number: u16,
+    // Kept sorted so that undoing a placement puts the piece back where it was
     pool: Vec<Piece>,
+    history: Vec<Action>,
+    undone: Vec<Action>,
+    // Hashes of every position reached, the starting one included, to find repetitions
+    positions: Vec<u64>,
+    // Zobrist hash of the pool and the player to move, the board keeps the one of the stacks
+    hash: u64,
+    rules: RuleSet,
+    // Behaviours can't be serialized, a deserialized game moves its bugs the default way
+    #[cfg_attr(feature = "serde", serde(skip))]
+    bugs: BugRegistry,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Action {
+    Put {
+        piece: Piece,
+        to: XYCoordinate,
+    },
+    Move {
+        piece: Piece,
+        from: XYCoordinate,
+        to: XYCoordinate,
+    },
+    // A piece carried over a pillbug (or a mosquito copying one) to another cell around it
+    Throw {
+        piece: Piece,
+        pillbug: XYCoordinate,
+        from: XYCoordinate,
+        to: XYCoordinate,
+    },
+    // Recorded when the player to move had no legal placement nor movement
+    Pass,
 }
 
 #[derive(Debug, PartialEq, Clone)]
-pub(crate) enum GameResult {
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum GameResult {
     Win(Color),
-    Draw,
+    Draw(DrawReason),
+}
+
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum DrawReason {
+    BothQueensSurrounded,
+    // Neither player can place nor move anything
+    NoLegalActions,
+    // The same position was reached for the third time
+    ThreefoldRepetition,
+    MoveLimit,
 }
 
 #[derive(Debug, PartialEq)]
-pub(crate) enum GameError {
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum GameError {
     NotYourTurn,
     NoPieceAtLocation,
     InvalidMove,
-    QueenMustBePlacedBeforeFifthTurn,
+    QueenMustBePlacedByDeadline,
+    QueenPlacedOnFirstTurn,
     SpawnedInOpponentsHive,
     SpawnedOnTopOfAnotherPiece,
     SpawnedOutOfHive,
@@ -39,21 +94,153 @@ pub(crate) enum GameError {
     PieceNotInPool,
     GameFinished(GameResult),
     MustPlaceBeeBeforeMoving,
+    NothingToUndo,
+    NothingToRedo,
+    NotAPillbug,
+    PieceJustMoved,
+    PlacedOutOfOrder,
+    // A homebrew bug that the game's BugRegistry doesn't know how to move
+    UnregisteredBug(Bug),
+}
+
+#[derive(Debug, PartialEq)]
+pub enum GameStringError {
+    UnknownGameType(String),
+    MissingField,
+    // The state or the turn don't match the ones the moves lead to
+    StateMismatch(String),
+    TurnMismatch(String),
+    // Moves are counted from 0
+    UnreadableMove { index: usize, error: NotationError },
+    IllegalMove { index: usize, error: GameError },
+    // Homebrew bugs move as their registry says, which can't be written down
+    HomebrewBug(Bug),
+}
+
+impl std::fmt::Display for GameResult {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GameResult::Win(color) => write!(f, "{color} won"),
+            GameResult::Draw(reason) => write!(f, "it's a draw, {reason}"),
+        }
+    }
+}
+
+impl std::fmt::Display for DrawReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let reason = match self {
+            DrawReason::BothQueensSurrounded => "both queens are surrounded",
+            DrawReason::NoLegalActions => "neither player can do anything",
+            DrawReason::ThreefoldRepetition => "the same position was reached three times",
+            DrawReason::MoveLimit => "the move limit was reached",
+        };
+        write!(f, "{reason}")
+    }
+}
+
+// Messages for the players, the variant names are for the code
+impl std::fmt::Display for GameError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let message = match self {
+            GameError::NotYourTurn => "It's not your turn",
+            GameError::NoPieceAtLocation => "There's no piece there",
+            GameError::InvalidMove => "That's not a valid move",
+            GameError::QueenMustBePlacedByDeadline => "The queen must be placed this turn",
+            GameError::QueenPlacedOnFirstTurn => "The queen can't be placed on the first turn",
+            GameError::SpawnedInOpponentsHive => "Pieces can't be placed next to the opponent's",
+            GameError::SpawnedOnTopOfAnotherPiece => "Pieces can't be placed on top of others",
+            GameError::SpawnedOutOfHive => "Pieces must be placed next to the hive",
+            GameError::HiveDisconnected => "Moving that piece would split the hive",
+            GameError::PieceNotInPool => "That piece isn't left to place",
+            GameError::GameFinished(result) => return write!(f, "The game is finished, {result}"),
+            GameError::MustPlaceBeeBeforeMoving => "The queen must be placed before moving",
+            GameError::NothingToUndo => "There's nothing to undo",
+            GameError::NothingToRedo => "There's nothing to redo",
+            GameError::NotAPillbug => "That piece can't throw others",
+            GameError::PieceJustMoved => "That piece was just moved",
+            GameError::PlacedOutOfOrder => {
+                "Pieces of the same bug are placed from the lowest number up"
+            }
+            GameError::UnregisteredBug(bug) => {
+                return write!(f, "The game doesn't know how {bug} moves")
+            }
+        };
+        write!(f, "{message}")
+    }
+}
+
+impl std::fmt::Display for GameStringError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GameStringError::UnknownGameType(game_type) => {
+                write!(f, "Unknown game type {game_type}")
+            }
+            GameStringError::MissingField => write!(f, "The game string is missing fields"),
+            GameStringError::StateMismatch(state) => {
+                write!(f, "The moves don't lead to the {state} state")
+            }
+            GameStringError::TurnMismatch(turn) => {
+                write!(f, "The moves don't lead to the {turn} turn")
+            }
+            // People count moves from 1
+            GameStringError::UnreadableMove { index, error } => {
+                write!(f, "Move {}: {error}", index + 1)
+            }
+            GameStringError::IllegalMove { index, error } => {
+                write!(f, "Move {}: {error}", index + 1)
+            }
+            GameStringError::HomebrewBug(bug) => {
+                write!(f, "Games with {bug} can't be written down")
+            }
+        }
+    }
 }
 
 impl Game {
-    pub(crate) fn new(pool: Vec<Piece>) -> Self {
-        Game {
-            turn: Color::Black,
+    pub fn new(rules: RuleSet) -> Self {
+        Self::with_bugs(rules, BugRegistry::default())
+    }
+
+    // A game where the pieces move as the given registry says
+    pub fn with_bugs(rules: RuleSet, bugs: BugRegistry) -> Self {
+        let mut pool = rules.pool();
+        pool.sort();
+        let mut game = Game {
+            turn: rules.first_player.clone(),
             result: None,
             board: StackableHexagonalBoard::new(GenericCoordinateSystem::new(
                 RELATIVE_NEIGHBORS_CLOCKWISE,
             )),
             turn_number: 1,
             pool,
-        }
+            history: vec![],
+            undone: vec![],
+            positions: vec![],
+            hash: 0,
+            rules,
+            bugs,
+        };
+        game.rehash();
+        game.positions.push(game.hash());
+        game
+    }
+
+    pub fn put(&mut self, piece: Piece, coordinate: XYCoordinate) -> Result<(), GameError> {
+        let index = self.check_put(&piece, coordinate)?;
+
+        self.pool.remove(index);
+        self.hash ^= pool_key(&piece);
+        self.board.put_piece(piece.clone(), coordinate);
+        self.history.push(Action::Put {
+            piece,
+            to: coordinate,
+        });
+        self.end_turn();
+        Ok(())
     }
-    pub(crate) fn put(&mut self, piece: Piece, coordinate: XYCoordinate) -> Result<(), GameError> {
+
+    // Validates a placement without performing it, returning the index of the piece in the pool
+    fn check_put(&self, piece: &Piece, coordinate: XYCoordinate) -> Result<usize, GameError> {
         if let Some(winner) = self.result.clone() {
             return Err(GameError::GameFinished(winner));
         }
@@ -62,6 +249,11 @@ impl Game {
             return Err(GameError::NotYourTurn);
         }
 
+        // Pieces that couldn't move once placed stay in the pool
+        if !self.bugs.knows(&piece.bug) {
+            return Err(GameError::UnregisteredBug(piece.bug.clone()));
+        }
+
         if self.board.get_top_piece(coordinate).is_some() {
             return Err(GameError::SpawnedOnTopOfAnotherPiece);
         }
@@ -76,438 +268,1931 @@ impl Game {
             return Err(GameError::SpawnedInOpponentsHive);
         }
 
-        // TODO: test this
         let colored_queen_is_not_placed = self
             .pool
             .iter()
             .any(|p| p.bug == Bug::Bee && p.color == self.turn);
 
-        let is_fourth_turn = self.turn_number == 7 || self.turn_number == 8;
+        let deadline_reached = self.player_turn() >= self.rules.queen_deadline.into();
 
-        if is_fourth_turn && piece.bug != Bug::Bee && colored_queen_is_not_placed {
-            return Err(GameError::QueenMustBePlacedBeforeFifthTurn);
+        if deadline_reached && piece.bug != Bug::Bee && colored_queen_is_not_placed {
+            return Err(GameError::QueenMustBePlacedByDeadline);
         }
 
-        if let Some(index) = self.pool.iter().position(|p| p == &piece) {
-            self.pool.swap_remove(index);
-        } else {
-            return Err(GameError::PieceNotInPool);
+        if self.rules.tournament_opening && self.player_turn() == 1 && piece.bug == Bug::Bee {
+            return Err(GameError::QueenPlacedOnFirstTurn);
         }
 
-        self.board.put_piece(piece, coordinate);
-        self.end_turn();
-        Ok(())
+        let index = self
+            .pool
+            .iter()
+            .position(|p| p == piece)
+            .ok_or(GameError::PieceNotInPool)?;
+
+        if self
+            .pool
+            .iter()
+            .any(|p| p.bug == piece.bug && p.color == piece.color && p.number < piece.number)
+        {
+            return Err(GameError::PlacedOutOfOrder);
+        }
+
+        Ok(index)
     }
 
     fn end_turn(&mut self) {
         // TODO: we are supposing that there are only 2 players
         // Once we extend the game to support more players, this will have to change
         let color_enclosed = [Color::Black, Color::White].map(|color| {
-            let bee = self.board.find(|p| p.color == color && p.bug == Bug::Bee);
-
-            let any_enclosed = bee
-                .iter()
-                .any(|&coordinate| self.board.neighbor_pieces(coordinate).len() == 6);
-
-            (color, any_enclosed)
+            let enclosed = self.around_bee(&color) == 6;
+            (color, enclosed)
         });
 
         match color_enclosed {
-            [(_, true), (_, true)] => self.result = Some(GameResult::Draw),
+            [(_, true), (_, true)] => {
+                self.result = Some(GameResult::Draw(DrawReason::BothQueensSurrounded))
+            }
             [(_, true), (color, _)] => self.result = Some(GameResult::Win(color)),
             [(color, _), (_, true)] => self.result = Some(GameResult::Win(color)),
             _ => {}
         }
 
-        self.turn = !self.turn.clone();
+        self.switch_turn();
         self.turn_number += 1;
+        self.undone.clear();
+        self.record_position();
+
+        if self.result.is_none() && !self.has_legal_action() {
+            self.skip_turn();
+        }
     }
 
-    pub(crate) fn move_top(
-        &mut self,
-        from: XYCoordinate,
-        to: XYCoordinate,
-    ) -> Result<(), GameError> {
-        if from == to {
-            return Err(GameError::InvalidMove);
+    // Passing is only valid when there's nothing else to do, and the game usually does it on its own
+    // It's left to the player after the automatic pass was taken back, see take_back_pass
+    fn pass(&mut self) -> Result<(), GameError> {
+        if let Some(result) = &self.result {
+            return Err(GameError::GameFinished(result.clone()));
         }
 
-        if let Some(winner) = &self.result {
-            return Err(GameError::GameFinished(winner.clone()));
+        if self.has_legal_action() {
+            return Err(GameError::InvalidMove);
         }
 
-        if self
-            .pool
-            .iter()
-            .filter(|p| p.color == self.turn)
-            .any(|p| p.bug == Bug::Bee)
-        {
-            return Err(GameError::MustPlaceBeeBeforeMoving);
+        self.undone.clear();
+        self.skip_turn();
+        Ok(())
+    }
+
+    fn skip_turn(&mut self) {
+        self.history.push(Action::Pass);
+        self.switch_turn();
+        self.turn_number += 1;
+        self.record_position();
+
+        // Neither player can do anything, so the game can't go on
+        if self.result.is_none() && !self.has_legal_action() {
+            self.result = Some(GameResult::Draw(DrawReason::NoLegalActions));
         }
+    }
 
-        let piece = self
-            .board
-            .get_top_piece(from)
-            .ok_or(GameError::NoPieceAtLocation)?;
+    // Remembers the position just reached, ending the game if it's reached for the third time or the turns ran out
+    fn record_position(&mut self) {
+        let hash = self.hash();
+        self.positions.push(hash);
 
-        if piece.color != self.turn {
-            return Err(GameError::NotYourTurn);
+        if self.result.is_some() {
+            return;
         }
 
-        let Ok(true) = self.can_move(from, to) else {
-            Err(GameError::InvalidMove)?
+        let repetitions = self.positions.iter().filter(|&&p| p == hash).count();
+        let out_of_turns = match self.rules.move_limit {
+            Some(limit) => self.history.len() >= 2 * limit as usize,
+            None => false,
         };
 
-        // TODO: remove repetitive errors
-        self.board
-            .move_top_piece(from, to)
-            .or_else(|_| Err(GameError::NoPieceAtLocation))?;
+        if self.rules.threefold_repetition && repetitions >= 3 {
+            self.result = Some(GameResult::Draw(DrawReason::ThreefoldRepetition));
+        } else if out_of_turns {
+            self.result = Some(GameResult::Draw(DrawReason::MoveLimit));
+        }
+    }
 
-        let hive = self.board.hive();
+    // Hashes the pool and the player to move from scratch, they are kept up to date from then on
+    fn rehash(&mut self) {
+        self.hash = self
+            .pool
+            .iter()
+            .fold(turn_key(&self.turn), |hash, piece| hash ^ pool_key(piece));
+    }
+
+    fn switch_turn(&mut self) {
+        let next = !self.turn.clone();
+        self.hash ^= turn_key(&self.turn) ^ turn_key(&next);
+        self.turn = next;
+    }
+
+    // How many turns the player to move has had, counting the current one
+    fn player_turn(&self) -> u16 {
+        (self.turn_number - 1) / 2 + 1
+    }
+
+    fn has_legal_action(&mut self) -> bool {
+        if self
+            .placeable_pieces(&self.turn)
+            .iter()
+            .any(|piece| self.can_place(piece))
+        {
+            return true;
+        }
+
+        !self.possible_movements().is_empty() || !self.possible_throws().is_empty()
+    }
 
-        // TODO: could this be done more efficiently?
-        let mut reachable = HashSet::new();
-        let mut to_visit = vec![to];
-        while let Some(coordinate) = to_visit.pop() {
-            reachable.insert(coordinate);
-            for neighbor in self
-                .board
-                .coordinate_system
-                .neighbor_coordinates(coordinate)
-            {
-                if !reachable.contains(&neighbor) && hive.contains(&neighbor) {
-                    to_visit.push(neighbor);
+    // Every placement and movement the player to move can make, sorted
+    // The very first placement is only offered at the origin, since the hive can start anywhere
+    pub fn legal_actions(&mut self) -> Vec<Action> {
+        let mut actions = vec![];
+
+        let cells = self.placement_cells();
+        for piece in self.placeable_pieces(&self.turn) {
+            for &to in &cells {
+                if self.check_put(&piece, to).is_ok() {
+                    actions.push(Action::Put {
+                        piece: piece.clone(),
+                        to,
+                    });
                 }
             }
         }
 
-        if hive != reachable {
-            self.board.move_top_piece(to, from).unwrap(); // TODO: remove unwrap
-            return Err(GameError::HiveDisconnected);
+        actions.extend(self.possible_movements());
+        actions.extend(self.possible_throws());
+
+        actions.sort();
+        actions
+    }
+
+    pub fn play(&mut self, action: Action) -> Result<(), GameError> {
+        match action {
+            Action::Put { piece, to } => self.put(piece, to),
+            Action::Move { piece, from, to } => {
+                if self.board.get_top_piece(from) != Some(&piece) {
+                    return Err(GameError::InvalidMove);
+                }
+                self.move_top(from, to)
+            }
+            Action::Throw {
+                piece,
+                pillbug,
+                from,
+                to,
+            } => {
+                if self.board.get_top_piece(from) != Some(&piece) {
+                    return Err(GameError::InvalidMove);
+                }
+                self.throw(pillbug, from, to)
+            }
+            Action::Pass => self.pass(),
         }
+    }
 
-        self.end_turn();
+    // Takes back the last action, along with the passes it forced
+    pub fn undo(&mut self) -> Result<(), GameError> {
+        let action = self.unmake()?;
+        self.undone.push(action);
         Ok(())
     }
 
-    fn can_move(&mut self, from: XYCoordinate, to: XYCoordinate) -> Result<bool, ()> {
-        Ok(self.possible_moves(from)?.contains(&to))
+    // Takes back the last action only if it's a pass, so the player to move is left to pass by themselves
+    pub fn take_back_pass(&mut self) -> Result<(), GameError> {
+        match self.history.last() {
+            Some(Action::Pass) => {
+                self.revert();
+                Ok(())
+            }
+            Some(_) => Err(GameError::InvalidMove),
+            None => Err(GameError::NothingToUndo),
+        }
     }
 
-    pub(crate) fn possible_moves(
-        &mut self,
-        from: XYCoordinate,
-    ) -> Result<HashSet<XYCoordinate>, ()> {
-        let piece = self.board.get_top_piece(from).ok_or(())?;
-
-        Ok(match piece.bug {
-            Bug::Bee => {
-                let walkable = self.board.walkable_without(from);
-
-                let hive = self.board.hive_without(from);
-
-                let neighbor_coordinates = self
-                    .board
-                    .coordinate_system
-                    .neighbor_coordinates(from)
-                    .into();
-                let slidable_neighbors =
-                    walkable.intersection(&neighbor_coordinates).filter(|&c| {
-                        self.board
-                            .coordinate_system
-                            .can_slide(from, *c, &hive)
-                            .unwrap() // TODO: dont unwrap
-                    });
+    pub fn redo(&mut self) -> Result<(), GameError> {
+        let action = self.undone.pop().ok_or(GameError::NothingToRedo)?;
 
-                slidable_neighbors.cloned().collect()
-            }
-            Bug::Beetle => self
-                .board
-                .hive_and_walkable_without(from)
-                .intersection(
-                    &self
-                        .board
-                        .coordinate_system
-                        .neighbor_coordinates(from)
-                        .into(),
-                )
-                .cloned()
-                .collect(),
-            Bug::Grasshopper => {
-                let hive = self.board.hive_without(from);
-
-                let possible_destinies = self
-                    .board
-                    .coordinate_system
-                    .relative_neighbors_clockwise()
-                    .into_iter()
-                    .flat_map(|direction| {
-                        let position = from + direction;
-
-                        if !hive.contains(&position) {
-                            return None;
-                        }
-
-                        let mut last = position;
-                        while hive.contains(&last) {
-                            last = last + direction;
-                        }
-                        Some(last)
-                    });
+        let result = self.make(action.clone());
+        if result.is_err() {
+            self.undone.push(action);
+        }
 
-                possible_destinies.collect()
-            }
-            Bug::Spider => {
-                let walkable = self.board.walkable_without(from);
+        result
+    }
 
-                let mut paths = vec![vec![from]];
+    // Leaves of the tree of legal actions the given number of actions deep, to check the rules against other engines
+    // The passes the game makes on its own belong to the action that forced them
+    pub fn perft(&mut self, depth: u8) -> Result<u64, GameError> {
+        if depth == 0 {
+            return Ok(1);
+        }
 
-                for _ in 0..3 {
-                    let mut new_paths = vec![];
+        let actions = self.legal_actions();
+        if depth == 1 {
+            return Ok(actions.len() as u64);
+        }
 
-                    for path in paths {
-                        let last = *path.last().ok_or(())?; // TODO: this should never fail
+        let mut leaves = 0;
+        for action in actions {
+            self.make(action)?;
+            leaves += self.perft(depth - 1)?;
+            self.unmake()?;
+        }
+        Ok(leaves)
+    }
 
-                        let neighbor_coordinates = self
-                            .board
-                            .coordinate_system
-                            .neighbor_coordinates(last)
-                            .into();
-                        let walkable_neighbors = walkable.intersection(&neighbor_coordinates);
-                        let slidable_neighbors = walkable_neighbors
-                            .filter(|&c| !path.contains(c))
-                            .filter(|&c| {
-                                self.board
-                                    .coordinate_system
-                                    .can_slide(last, *c, &walkable)
-                                    .unwrap() // TODO: dont unwrap
-                            });
-
-                        for &neighbor in slidable_neighbors {
-                            let mut new_path = path.clone();
-                            new_path.push(neighbor);
-                            new_paths.push(new_path);
-                        }
-                    }
+    // The perft leaves under each legal action, to find the one where the counts disagree
+    pub fn perft_divide(&mut self, depth: u8) -> Result<Vec<(Action, u64)>, GameError> {
+        if depth == 0 {
+            return Ok(vec![]);
+        }
 
-                    paths = new_paths;
-                }
+        let mut divided = vec![];
+        for action in self.legal_actions() {
+            self.make(action.clone())?;
+            let leaves = self.perft(depth - 1)?;
+            self.unmake()?;
+            divided.push((action, leaves));
+        }
+        Ok(divided)
+    }
 
-                paths.iter().flat_map(|p| p.last()).cloned().collect()
-            }
-            Bug::Ant => {
-                let walkable = self.board.walkable_without(from);
+    // Plays an action without forgetting the actions that can be redone, for looking ahead
+    pub fn make(&mut self, action: Action) -> Result<(), GameError> {
+        // Playing clears the actions that can be redone, so we keep them aside
+        let undone = std::mem::take(&mut self.undone);
+        let result = self.play(action);
+        self.undone = undone;
 
-                // Traverse the tree
-                let mut reachable: HashSet<XYCoordinate> = HashSet::new();
-                let mut to_check = vec![from];
+        result
+    }
 
-                while let Some(current) = to_check.pop() {
-                    let neighbor_coordinates = self
-                        .board
-                        .coordinate_system
-                        .neighbor_coordinates(current)
-                        .into();
-                    let slidable_neighbors =
-                        walkable.intersection(&neighbor_coordinates).filter(|&&c| {
-                            self.board
-                                .coordinate_system
-                                .can_slide(current, c, &self.board.hive())
-                                .unwrap() // TODO: dont unwrap
-                        });
+    // Takes back the last action like undo, but it can't be redone
+    pub fn unmake(&mut self) -> Result<Action, GameError> {
+        while let Some(Action::Pass) = self.history.last() {
+            self.revert();
+        }
 
-                    for &neighbor in slidable_neighbors {
-                        if !reachable.contains(&neighbor) {
-                            to_check.push(neighbor);
-                        }
-                    }
+        self.revert().ok_or(GameError::NothingToUndo)
+    }
 
-                    reachable.insert(current);
-                }
+    fn revert(&mut self) -> Option<Action> {
+        let action = self.history.pop()?;
+        self.positions.pop();
 
-                reachable
+        match &action {
+            Action::Put { piece, to } => {
+                self.board.take_top_piece(*to);
+                let index = self.pool.binary_search(piece).unwrap_or_else(|i| i);
+                self.pool.insert(index, piece.clone());
+                self.hash ^= pool_key(piece);
             }
-        })
-    }
+            Action::Move { from, to, .. } | Action::Throw { from, to, .. } => {
+                self.board
+                    .move_top_piece(*to, *from)
+                    .expect("the piece that moved is on top of where it went");
+            }
+            Action::Pass => {}
+        }
 
-    pub(crate) fn get_pool(&self) -> &Vec<Piece> {
-        &self.pool
-    }
+        // Actions can only be made while the game is not finished
+        self.result = None;
+        self.switch_turn();
+        self.turn_number -= 1;
 
-    pub(crate) fn default_pool() -> Vec<Piece> {
-        [Color::Black, Color::White]
-            .iter()
-            .flat_map(|color| {
-                vec![
-                    (
-                        1,
-                        Piece {
-                            bug: Bug::Bee,
-                            color: color.clone(),
-                        },
-                    ),
-                    (
-                        2,
-                        Piece {
-                            bug: Bug::Beetle,
-                            color: color.clone(),
-                        },
-                    ),
-                    (
-                        2,
-                        Piece {
-                            bug: Bug::Spider,
-                            color: color.clone(),
-                        },
-                    ),
-                    (
-                        3,
-                        Piece {
-                            bug: Bug::Ant,
-                            color: color.clone(),
-                        },
-                    ),
-                    (
-                        3,
-                        Piece {
-                            bug: Bug::Grasshopper,
-                            color: color.clone(),
-                        },
-                    ),
-                ]
-            })
-            .flat_map(|(count, piece)| (0..count).map(move |_| piece.clone()))
-            .collect()
+        Some(action)
     }
 
-    pub(crate) fn get_top_piece(&self, coordinate: XYCoordinate) -> Option<&Piece> {
-        self.board.get_top_piece(coordinate)
+    // The lowest numbered piece of each bug the color has left in the pool
+    pub fn placeable_pieces(&self, color: &Color) -> Vec<Piece> {
+        let mut pieces: Vec<Piece> = vec![];
+        for piece in self.pool.iter().filter(|p| &p.color == color) {
+            if !pieces.iter().any(|p| p.bug == piece.bug) {
+                pieces.push(piece.clone());
+            }
+        }
+        pieces
     }
 
-    pub(crate) fn hive(&self) -> HashSet<XYCoordinate> {
-        self.board.hive()
+    // Whether the piece can be placed anywhere this turn
+    pub fn can_place(&self, piece: &Piece) -> bool {
+        self.placement_cells()
+            .into_iter()
+            .any(|c| self.check_put(piece, c).is_ok())
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    // Empty cells touching the hive, or the origin if nothing has been placed yet
+    fn placement_cells(&self) -> HashSet<XYCoordinate> {
+        let hive = self.board.hive();
 
-    #[test]
-    fn simple_game() {
-        let mut game = Game::new(Game::default_pool());
+        if hive.is_empty() {
+            return HashSet::from([(0, 0).into()]);
+        }
 
-        let black_bee = Piece {
-            bug: Bug::Bee,
-            color: Color::Black,
-        };
-        let white_bee = Piece {
-            bug: Bug::Bee,
-            color: Color::White,
-        };
-        let black_beetle = Piece {
-            bug: Bug::Beetle,
-            color: Color::Black,
-        };
-        let white_beetle = Piece {
-            bug: Bug::Beetle,
-            color: Color::White,
-        };
-        let black_ant = Piece {
-            bug: Bug::Ant,
-            color: Color::Black,
-        };
+        hive.iter()
+            .flat_map(|&c| self.board.coordinate_system.neighbor_coordinates(c))
+            .filter(|c| !hive.contains(c))
+            .collect()
+    }
 
-        game.put(black_bee.clone(), (0, 0).into()).unwrap(); // black bee is placed at (0, 0)
+    pub fn move_top(&mut self, from: XYCoordinate, to: XYCoordinate) -> Result<(), GameError> {
+        self.check_move(from, to)?;
 
-        assert_eq!(
-            game.put(black_bee.clone(), (0, 0).into()),
-            Err(GameError::NotYourTurn)
-        ); // it's not black's turn
+        // TODO: remove repetitive errors
+        let piece = self
+            .board
+            .get_top_piece(from)
+            .ok_or(GameError::NoPieceAtLocation)?
+            .clone();
+        self.board
+            .move_top_piece(from, to)
+            .map_err(|_| GameError::NoPieceAtLocation)?;
+        self.history.push(Action::Move { piece, from, to });
 
-        game.put(white_bee.clone(), (1, 0).into()).unwrap(); // white bee is placed at (1, 0)
+        self.end_turn();
+        Ok(())
+    }
 
-        assert_eq!(
-            game.put(black_beetle.clone(), (2, 0).into()),
-            Err(GameError::SpawnedInOpponentsHive)
-        ); // black beetle cannot spawn in white's hive
+    // Validates a movement without performing it
+    fn check_move(&mut self, from: XYCoordinate, to: XYCoordinate) -> Result<(), GameError> {
+        if from == to {
+            return Err(GameError::InvalidMove);
+        }
 
-        assert_eq!(
-            game.put(black_beetle.clone(), (0, 0).into()),
-            Err(GameError::SpawnedOnTopOfAnotherPiece)
-        ); // pieces cannot spawn on top of another piece
+        self.check_mover(from)?;
 
-        game.put(black_beetle.clone(), (-1, 0).into()).unwrap(); // black beetle is placed at (-1, 0)
+        if self.board.pinned().contains(&from) {
+            return Err(GameError::HiveDisconnected);
+        }
 
-        game.put(white_beetle.clone(), (2, 0).into()).unwrap(); // white beetle is placed at (2, 0)
-        game.put(black_ant.clone(), (-1, 1).into()).unwrap(); // black ant is placed at (-1, 1)
+        if !self.can_move(from, to)? {
+            return Err(GameError::InvalidMove);
+        }
 
-        game.move_top((2, 0).into(), (1, 0).into()).unwrap(); // white beetle moves to (1, 0), stacking on top of the white bee
+        Ok(())
+    }
 
-        game.move_top((-1, 1).into(), (1, 1).into()).unwrap(); // black ant moves to (1, 1)
+    // Validates that the piece at 'from' can be moved this turn, wherever it goes
+    fn check_mover(&self, from: XYCoordinate) -> Result<(), GameError> {
+        if let Some(winner) = &self.result {
+            return Err(GameError::GameFinished(winner.clone()));
+        }
 
-        assert_eq!(
-            game.move_top((1, 0).into(), (1, 0).into()),
-            Err(GameError::InvalidMove)
-        ); // cannot move to the same location
+        if self
+            .pool
+            .iter()
+            .filter(|p| p.color == self.turn)
+            .any(|p| p.bug == Bug::Bee)
+        {
+            return Err(GameError::MustPlaceBeeBeforeMoving);
+        }
 
-        game.move_top((1, 0).into(), (0, 0).into()).unwrap(); // white beetle moves to (0, 0), stacking on top of the black beetle
+        let piece = self
+            .board
+            .get_top_piece(from)
+            .ok_or(GameError::NoPieceAtLocation)?;
 
-        assert_eq!(
+        if piece.color != self.turn {
+            return Err(GameError::NotYourTurn);
+        }
+
+        if self.last_moved() == Some(from) {
+            return Err(GameError::PieceJustMoved);
+        }
+
+        Ok(())
+    }
+
+    // Every movement the player to move can make
+    // The pinned pieces are found once for the whole position instead of once per movement
+    fn possible_movements(&mut self) -> Vec<Action> {
+        let pinned = self.board.pinned();
+
+        let mut movements = vec![];
+        for from in self.board.find(|p| p.color == self.turn) {
+            if pinned.contains(&from) || self.check_mover(from).is_err() {
+                continue;
+            }
+
+            let Some(piece) = self.board.get_top_piece(from).cloned() else {
+                continue;
+            };
+            let Ok(destinations) = self.bugs.possible_moves(&piece.bug, &mut self.board, from)
+            else {
+                continue;
+            };
+            for to in destinations {
+                if to != from {
+                    movements.push(Action::Move {
+                        piece: piece.clone(),
+                        from,
+                        to,
+                    });
+                }
+            }
+        }
+        movements
+    }
+
+    // Moves the piece at 'from' over the pillbug at 'pillbug' into the empty cell at 'to'
+    pub fn throw(
+        &mut self,
+        pillbug: XYCoordinate,
+        from: XYCoordinate,
+        to: XYCoordinate,
+    ) -> Result<(), GameError> {
+        self.check_throw(pillbug, from, to)?;
+
+        let piece = self
+            .board
+            .get_top_piece(from)
+            .ok_or(GameError::NoPieceAtLocation)?
+            .clone();
+        self.board
+            .move_top_piece(from, to)
+            .map_err(|_| GameError::NoPieceAtLocation)?;
+        self.history.push(Action::Throw {
+            piece,
+            pillbug,
+            from,
+            to,
+        });
+
+        self.end_turn();
+        Ok(())
+    }
+
+    // Validates a throw without performing it
+    fn check_throw(
+        &mut self,
+        pillbug: XYCoordinate,
+        from: XYCoordinate,
+        to: XYCoordinate,
+    ) -> Result<(), GameError> {
+        if let Some(winner) = &self.result {
+            return Err(GameError::GameFinished(winner.clone()));
+        }
+
+        if self
+            .pool
+            .iter()
+            .filter(|p| p.color == self.turn)
+            .any(|p| p.bug == Bug::Bee)
+        {
+            return Err(GameError::MustPlaceBeeBeforeMoving);
+        }
+
+        let thrower = self
+            .board
+            .get_top_piece(pillbug)
+            .ok_or(GameError::NoPieceAtLocation)?;
+
+        if thrower.color != self.turn {
+            return Err(GameError::NotYourTurn);
+        }
+
+        if !self.can_throw(pillbug) {
+            return Err(GameError::NotAPillbug);
+        }
+
+        self.board
+            .get_top_piece(from)
+            .ok_or(GameError::NoPieceAtLocation)?;
+
+        let last_moved = self.last_moved();
+        if last_moved == Some(pillbug) || last_moved == Some(from) {
+            return Err(GameError::PieceJustMoved);
+        }
+
+        let around = self.board.coordinate_system.neighbor_coordinates(pillbug);
+        if from == to || !around.contains(&from) || !around.contains(&to) {
+            return Err(GameError::InvalidMove);
+        }
+
+        // Only unstacked pieces can be thrown, and only into empty cells
+        if self.board.height(from) != 1 || self.board.height(to) != 0 {
+            return Err(GameError::InvalidMove);
+        }
+
+        // The thrown piece climbs onto the pillbug and then goes down, so both steps go through gates
+        let piece = self
+            .board
+            .take_top_piece(from)
+            .ok_or(GameError::NoPieceAtLocation)?;
+        let through_gates = self.board.can_step(from, pillbug).unwrap_or(false)
+            && self.board.can_step(pillbug, to).unwrap_or(false);
+        self.board.put_piece(piece, from);
+
+        if !through_gates {
+            return Err(GameError::InvalidMove);
+        }
+
+        if self.board.pinned().contains(&from) {
+            return Err(GameError::HiveDisconnected);
+        }
+
+        Ok(())
+    }
+
+    // Pillbugs can throw, and so can mosquitoes on the ground touching one
+    fn can_throw(&self, coordinate: XYCoordinate) -> bool {
+        match self.board.get_top_piece(coordinate) {
+            Some(Piece {
+                bug: Bug::Pillbug, ..
+            }) => true,
+            Some(Piece {
+                bug: Bug::Mosquito, ..
+            }) => {
+                self.board.height(coordinate) == 1
+                    && self
+                        .board
+                        .neighbor_pieces(coordinate)
+                        .iter()
+                        .any(|p| p.bug == Bug::Pillbug)
+            }
+            _ => false,
+        }
+    }
+
+    fn possible_throws(&mut self) -> Vec<Action> {
+        let pillbugs: Vec<XYCoordinate> = self
+            .board
+            .find(|p| p.color == self.turn)
+            .into_iter()
+            .filter(|&c| self.can_throw(c))
+            .collect();
+
+        let mut throws = vec![];
+        for pillbug in pillbugs {
+            let around = self.board.coordinate_system.neighbor_coordinates(pillbug);
+            for from in around {
+                for to in around {
+                    if self.check_throw(pillbug, from, to).is_ok() {
+                        let piece = self
+                            .board
+                            .get_top_piece(from)
+                            .expect("valid throws have a piece to throw")
+                            .clone();
+                        throws.push(Action::Throw {
+                            piece,
+                            pillbug,
+                            from,
+                            to,
+                        });
+                    }
+                }
+            }
+        }
+        throws
+    }
+
+    // Where the piece placed or moved in the previous turn is
+    // That piece can't be moved nor thrown in the current turn
+    fn last_moved(&self) -> Option<XYCoordinate> {
+        match self.history.last()? {
+            Action::Put { to, .. } | Action::Move { to, .. } | Action::Throw { to, .. } => {
+                Some(*to)
+            }
+            Action::Pass => None,
+        }
+    }
+
+    fn can_move(&mut self, from: XYCoordinate, to: XYCoordinate) -> Result<bool, GameError> {
+        Ok(self.possible_moves(from)?.contains(&to))
+    }
+
+    pub fn possible_moves(
+        &mut self,
+        from: XYCoordinate,
+    ) -> Result<HashSet<XYCoordinate>, GameError> {
+        let bug = self
+            .board
+            .get_top_piece(from)
+            .ok_or(GameError::NoPieceAtLocation)?
+            .bug
+            .clone();
+
+        // A pinned piece can't go anywhere, whatever its bug
+        if self.board.pinned().contains(&from) {
+            return Ok(HashSet::new());
+        }
+
+        self.bugs.possible_moves(&bug, &mut self.board, from)
+    }
+
+    // Like the hash, but the same for positions that only differ in where the hive is and how it's turned or mirrored
+    pub fn canonical_hash(&self) -> u64 {
+        self.board.canonical_hash() ^ self.hash
+    }
+
+    pub fn get_pool(&self) -> &Vec<Piece> {
+        &self.pool
+    }
+
+    pub fn history(&self) -> &Vec<Action> {
+        &self.history
+    }
+
+    pub fn turn(&self) -> &Color {
+        &self.turn
+    }
+
+    pub fn result(&self) -> Option<&GameResult> {
+        self.result.as_ref()
+    }
+
+    // How many cells around the color's bee are taken, none while it's in the pool
+    // The bee counts even when there are beetles on top of it
+    pub fn around_bee(&self, color: &Color) -> usize {
+        self.board
+            .hive()
+            .into_iter()
+            .filter(|&c| {
+                self.stack(c)
+                    .iter()
+                    .any(|p| &p.color == color && p.bug == Bug::Bee)
+            })
+            .map(|c| self.board.neighbor_pieces(c).len())
+            .max()
+            .unwrap_or(0)
+    }
+
+    // Zobrist hash of the position: the stacks, the pieces left to place and the player to move
+    pub fn hash(&self) -> u64 {
+        self.board.hash() ^ self.hash
+    }
+
+    pub fn rules(&self) -> &RuleSet {
+        &self.rules
+    }
+
+    // The UHP GameString: game type, state, turn and every move, e.g. "Base+M;InProgress;White[2];wS1;bM wS1-"
+    // Only the expansions are written, the other rules are kept apart, see from_game_string_with_rules
+    pub fn to_game_string(&self) -> Result<String, GameStringError> {
+        Ok([self.game_type()?, self.state(), self.turn_string()]
+            .into_iter()
+            .chain(notation::history(self))
+            .collect::<Vec<_>>()
+            .join(";"))
+    }
+
+    // A new game from a UHP GameTypeString like "Base+MLP", played with the rules of UHP games
+    pub fn from_game_type(game_type: &str) -> Result<Self, GameStringError> {
+        Self::from_game_type_with_rules(game_type, RuleSet::uhp())
+    }
+
+    // A new game with the expansions of the GameTypeString and the rest of the given rules
+    pub fn from_game_type_with_rules(
+        game_type: &str,
+        rules: RuleSet,
+    ) -> Result<Self, GameStringError> {
+        let unknown = || GameStringError::UnknownGameType(game_type.to_string());
+
+        let letters = match game_type.split_once('+') {
+            _ if game_type == "Base" => "",
+            Some(("Base", letters)) if !letters.is_empty() => letters,
+            _ => return Err(unknown()),
+        };
+
+        let mut expansions = vec![];
+        let mut remaining = EXPANSIONS.iter();
+        for letter in letters.chars() {
+            // Each expansion at most once, in the same order as EXPANSIONS
+            let bug = remaining
+                .find(|bug| notation::bug_letter(bug) == letter)
+                .ok_or_else(unknown)?;
+            expansions.push(bug.clone());
+        }
+
+        Ok(Self::new(RuleSet {
+            expansions,
+            ..rules
+        }))
+    }
+
+    // Replays the moves of a UHP GameString, played with the rules of UHP games
+    pub fn from_game_string(game_string: &str) -> Result<Self, GameStringError> {
+        Self::from_game_string_with_rules(game_string, RuleSet::uhp())
+    }
+
+    // Replays the moves of a UHP GameString with the given rules, failing at the first move that can't be played
+    // The expansions come from the GameString, the rules only tell the rest
+    pub fn from_game_string_with_rules(
+        game_string: &str,
+        rules: RuleSet,
+    ) -> Result<Self, GameStringError> {
+        let mut fields = game_string.split(';');
+        let game_type = fields.next().ok_or(GameStringError::MissingField)?;
+        let state = fields.next().ok_or(GameStringError::MissingField)?;
+        let turn = fields.next().ok_or(GameStringError::MissingField)?;
+
+        let mut game = Self::from_game_type_with_rules(game_type, rules)?;
+
+        let moves: Vec<&str> = fields.collect();
+        for (index, &text) in moves.iter().enumerate() {
+            // Passes are played by the game itself, so they only have to be where the game put them
+            let passed = game.history().get(index) == Some(&Action::Pass);
+            match (passed, text == "pass") {
+                (true, true) => continue,
+                (false, false) => {}
+                _ => {
+                    return Err(GameStringError::IllegalMove {
+                        index,
+                        error: GameError::InvalidMove,
+                    })
+                }
+            }
+
+            let action = notation::parse(&mut game, text).map_err(|error| match error {
+                NotationError::Illegal(error) => GameStringError::IllegalMove { index, error },
+                error => GameStringError::UnreadableMove { index, error },
+            })?;
+            game.play(action)
+                .map_err(|error| GameStringError::IllegalMove { index, error })?;
+        }
+
+        // The string can end before the pass the game made, leaving the player to pass next
+        if game.history().len() > moves.len() {
+            game.take_back_pass()
+                .map_err(|error| GameStringError::IllegalMove {
+                    index: moves.len(),
+                    error,
+                })?;
+        }
+
+        if game.state() != state {
+            return Err(GameStringError::StateMismatch(state.to_string()));
+        }
+        if game.turn_string() != turn {
+            return Err(GameStringError::TurnMismatch(turn.to_string()));
+        }
+
+        Ok(game)
+    }
+
+    fn game_type(&self) -> Result<String, GameStringError> {
+        let rules = &self.rules;
+        if let Some(bug) = rules
+            .expansions
+            .iter()
+            .find(|bug| !EXPANSIONS.contains(bug))
+        {
+            return Err(GameStringError::HomebrewBug(bug.clone()));
+        }
+
+        let letters: String = EXPANSIONS
+            .iter()
+            .filter(|bug| rules.expansions.contains(bug))
+            .map(notation::bug_letter)
+            .collect();
+
+        match letters.is_empty() {
+            true => Ok("Base".to_string()),
+            false => Ok(format!("Base+{letters}")),
+        }
+    }
+
+    fn state(&self) -> String {
+        match &self.result {
+            _ if self.history.is_empty() => "NotStarted",
+            None => "InProgress",
+            Some(GameResult::Draw(_)) => "Draw",
+            Some(GameResult::Win(Color::White)) => "WhiteWins",
+            Some(GameResult::Win(Color::Black)) => "BlackWins",
+        }
+        .to_string()
+    }
+
+    // The color to move and the turn number, both players moving once per turn, e.g. "Black[3]"
+    fn turn_string(&self) -> String {
+        let color = match self.turn {
+            Color::White => "White",
+            Color::Black => "Black",
+        };
+        format!("{color}[{}]", self.history.len() / 2 + 1)
+    }
+
+    // Where the piece is, as long as there's nothing on top of it
+    pub fn position(&self, piece: &Piece) -> Option<XYCoordinate> {
+        self.board.find(|p| p == piece).first().copied()
+    }
+
+    pub fn get_top_piece(&self, coordinate: XYCoordinate) -> Option<&Piece> {
+        self.board.get_top_piece(coordinate)
+    }
+
+    pub fn hive(&self) -> HashSet<XYCoordinate> {
+        self.board.hive()
+    }
+
+    // Pieces from the bottom to the top of the stack, empty if there's none
+    pub fn stack(&self, coordinate: XYCoordinate) -> &[Piece] {
+        self.board.get_cell(coordinate).map_or(&[], |cell| cell)
+    }
+
+    // Cells whose pieces can't move without breaking the hive
+    pub fn pinned(&self) -> HashSet<XYCoordinate> {
+        self.board.pinned()
+    }
+}
+
+// Zobrist keys of a piece left to place and of the player to move, the stacks have theirs in the board
+// The tags keep the keys of the pool apart from the ones of the turn
+fn pool_key(piece: &Piece) -> u64 {
+    splitmix(&[1, piece.zobrist_id()])
+}
+
+fn turn_key(color: &Color) -> u64 {
+    splitmix(&[2, color.zobrist_id()])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_positions::WIN_IN_ONE;
+
+    // A game with only the given pieces to place
+    fn with_pool(mut pool: Vec<Piece>) -> Game {
+        pool.sort();
+        let mut game = Game {
+            pool,
+            ..Game::new(RuleSet::default())
+        };
+        game.rehash();
+        game.positions = vec![game.hash()];
+        game
+    }
+
+    #[test]
+    fn simple_game() {
+        let mut game = Game::new(RuleSet::default());
+
+        let black_bee = Piece {
+            bug: Bug::Bee,
+            color: Color::Black,
+            number: 1,
+        };
+        let white_bee = Piece {
+            bug: Bug::Bee,
+            color: Color::White,
+            number: 1,
+        };
+        let black_beetle = Piece {
+            bug: Bug::Beetle,
+            color: Color::Black,
+            number: 1,
+        };
+        let white_beetle = Piece {
+            bug: Bug::Beetle,
+            color: Color::White,
+            number: 1,
+        };
+        let black_ant = Piece {
+            bug: Bug::Ant,
+            color: Color::Black,
+            number: 1,
+        };
+
+        game.put(black_bee.clone(), (0, 0).into()).unwrap(); // black bee is placed at (0, 0)
+
+        assert_eq!(
+            game.put(black_bee.clone(), (0, 0).into()),
+            Err(GameError::NotYourTurn)
+        ); // it's not black's turn
+
+        game.put(white_bee.clone(), (1, 0).into()).unwrap(); // white bee is placed at (1, 0)
+
+        assert_eq!(
+            game.put(black_beetle.clone(), (2, 0).into()),
+            Err(GameError::SpawnedInOpponentsHive)
+        ); // black beetle cannot spawn in white's hive
+
+        assert_eq!(
+            game.put(black_beetle.clone(), (0, 0).into()),
+            Err(GameError::SpawnedOnTopOfAnotherPiece)
+        ); // pieces cannot spawn on top of another piece
+
+        game.put(black_beetle.clone(), (-1, 0).into()).unwrap(); // black beetle is placed at (-1, 0)
+
+        game.put(white_beetle.clone(), (2, 0).into()).unwrap(); // white beetle is placed at (2, 0)
+        game.put(black_ant.clone(), (-1, 1).into()).unwrap(); // black ant is placed at (-1, 1)
+
+        game.move_top((2, 0).into(), (1, 0).into()).unwrap(); // white beetle moves to (1, 0), stacking on top of the white bee
+
+        game.move_top((-1, 1).into(), (1, 1).into()).unwrap(); // black ant moves to (1, 1)
+
+        assert_eq!(
+            game.move_top((1, 0).into(), (1, 0).into()),
+            Err(GameError::InvalidMove)
+        ); // cannot move to the same location
+
+        game.move_top((1, 0).into(), (0, 0).into()).unwrap(); // white beetle moves to (0, 0), stacking on top of the black beetle
+
+        assert_eq!(
             game.move_top((-10, -10).into(), (5, 0).into()),
             Err(GameError::NoPieceAtLocation)
         );
     }
 
     #[test]
-    fn bee_gets_trapped() {
-        let mut game = Game::new(Game::default_pool());
+    fn bee_gets_trapped() {
+        let mut game = Game::new(RuleSet::default());
+
+        let black_bee = Piece {
+            bug: Bug::Bee,
+            color: Color::Black,
+            number: 1,
+        };
+        let white_bee = Piece {
+            bug: Bug::Bee,
+            color: Color::White,
+            number: 1,
+        };
+        let black_beetle = Piece {
+            bug: Bug::Beetle,
+            color: Color::Black,
+            number: 1,
+        };
+        let white_beetle = Piece {
+            bug: Bug::Beetle,
+            color: Color::White,
+            number: 1,
+        };
+        let black_ant = Piece {
+            bug: Bug::Ant,
+            color: Color::Black,
+            number: 1,
+        };
+        let white_ant = Piece {
+            bug: Bug::Ant,
+            color: Color::White,
+            number: 1,
+        };
+        let white_grasshopper = Piece {
+            bug: Bug::Grasshopper,
+            color: Color::White,
+            number: 1,
+        };
+
+        game.put(black_bee.clone(), (0, 0).into()).unwrap(); // black bee is placed at (0, 0)
+        game.put(white_bee.clone(), (1, 0).into()).unwrap(); // white bee is placed at (1, 0)
+        game.put(black_beetle.clone(), (-1, 1).into()).unwrap(); // black beetle is placed at (-1, 1)
+        game.put(white_beetle.clone(), (2, 0).into()).unwrap(); // white beetle is placed at (2, 0)
+        game.put(black_ant.clone(), (0, -1).into()).unwrap(); // black ant is placed at (0, -1)
+        game.put(white_grasshopper.clone(), (1, 1).into()).unwrap(); // white grasshopper is placed at (1, 1)
+
+        game.move_top((-1, 1).into(), (0, 1).into()).unwrap(); // black beetle moves to (0, 1)
+
+        game.put(white_ant.clone(), (2, -1).into()).unwrap(); // white ant is placed at (2, -1)
+
+        game.move_top((0, -1).into(), (1, -1).into()).unwrap(); // black ant moves to (1, -1)
+
+        assert_eq!(
+            game.move_top((1, 1).into(), (0, 1).into()),
+            Err(GameError::GameFinished(GameResult::Win(Color::Black)))
+        ); // white grasshopper cannot move to (0, 1) because the black bee is trapped
+    }
+
+    #[test]
+    fn player_without_actions_passes() {
+        let black_bee = Piece {
+            bug: Bug::Bee,
+            color: Color::Black,
+            number: 1,
+        };
+        let white_bee = Piece {
+            bug: Bug::Bee,
+            color: Color::White,
+            number: 1,
+        };
+        let white_beetle = Piece {
+            bug: Bug::Beetle,
+            color: Color::White,
+            number: 1,
+        };
+
+        let mut game = with_pool(vec![
+            black_bee.clone(),
+            white_bee.clone(),
+            white_beetle.clone(),
+        ]);
+
+        game.put(black_bee.clone(), (0, 0).into()).unwrap(); // black bee is placed at (0, 0)
+        game.put(white_bee.clone(), (1, 0).into()).unwrap(); // white bee is placed at (1, 0)
+        game.move_top((0, 0).into(), (0, 1).into()).unwrap(); // black bee moves to (0, 1)
+        game.put(white_beetle.clone(), (2, 0).into()).unwrap(); // white beetle is placed at (2, 0)
+        game.move_top((0, 1).into(), (1, 1).into()).unwrap(); // black bee moves to (1, 1)
+
+        assert_eq!(
+            game.history().last(),
+            Some(&Action::Move {
+                piece: black_bee.clone(),
+                from: (0, 1).into(),
+                to: (1, 1).into(),
+            })
+        );
+
+        game.move_top((2, 0).into(), (1, 1).into()).unwrap(); // white beetle climbs on top of the black bee
+
+        assert_eq!(game.history().last(), Some(&Action::Pass)); // black has nothing to place nor move
+        assert_eq!(game.turn(), &Color::White);
+        assert_eq!(game.result, None);
+
+        game.take_back_pass().unwrap();
+        assert_eq!(game.turn(), &Color::Black);
+        assert_eq!(game.legal_actions(), vec![]);
+        assert_eq!(game.take_back_pass(), Err(GameError::InvalidMove)); // only passes are taken back
+
+        game.play(Action::Pass).unwrap(); // black passes by itself
+        assert_eq!(game.history().last(), Some(&Action::Pass));
+        assert_eq!(game.play(Action::Pass), Err(GameError::InvalidMove)); // white has moves
+
+        game.move_top((1, 1).into(), (0, 1).into()).unwrap(); // white beetle moves down to (0, 1)
+
+        assert_eq!(game.turn(), &Color::Black); // black bee is free again
+    }
+
+    #[test]
+    fn legal_actions_agree_with_put_and_move_top() {
+        let mut game = Game::new(RuleSet::default());
+
+        let black_bee = Piece {
+            bug: Bug::Bee,
+            color: Color::Black,
+            number: 1,
+        };
+        let white_bee = Piece {
+            bug: Bug::Bee,
+            color: Color::White,
+            number: 1,
+        };
+
+        assert_eq!(game.legal_actions().len(), 5); // every bug can start the hive at the origin
+
+        game.put(black_bee.clone(), (0, 0).into()).unwrap(); // black bee is placed at (0, 0)
+
+        assert_eq!(game.legal_actions().len(), 5 * 6); // every bug around the black bee
+
+        game.put(white_bee.clone(), (1, 0).into()).unwrap(); // white bee is placed at (1, 0)
+
+        let actions = game.legal_actions();
+        let placements = actions
+            .iter()
+            .filter(|a| matches!(a, Action::Put { .. }))
+            .count();
+        assert_eq!(placements, 4 * 3); // 4 bugs in 3 cells not touching white
+        assert!(actions.contains(&Action::Move {
+            piece: black_bee.clone(),
+            from: (0, 0).into(),
+            to: (0, 1).into(),
+        }));
+        assert!(actions.contains(&Action::Move {
+            piece: black_bee.clone(),
+            from: (0, 0).into(),
+            to: (1, -1).into(),
+        }));
+        assert_eq!(actions.len(), placements + 2);
+
+        for action in actions {
+            let mut after = game.clone();
+            assert_eq!(after.play(action), Ok(()));
+        }
+    }
+
+    #[test]
+    fn legal_actions_force_the_queen_on_the_fourth_turn() {
+        let mut game = Game::new(RuleSet::default());
+
+        let black_ant = Piece {
+            bug: Bug::Ant,
+            color: Color::Black,
+            number: 1,
+        };
+        let white_ant = Piece {
+            bug: Bug::Ant,
+            color: Color::White,
+            number: 1,
+        };
+
+        for number in 1..=3 {
+            game.put(
+                Piece {
+                    number,
+                    ..black_ant.clone()
+                },
+                (1 - number as i8, 0).into(),
+            )
+            .unwrap();
+            game.put(
+                Piece {
+                    number,
+                    ..white_ant.clone()
+                },
+                (number as i8, 0).into(),
+            )
+            .unwrap();
+        }
+
+        let actions = game.legal_actions();
+
+        assert!(!actions.is_empty());
+        assert!(actions.iter().all(|a| matches!(
+            a,
+            Action::Put {
+                piece: Piece { bug: Bug::Bee, .. },
+                ..
+            }
+        ))); // black can neither move nor place anything but the bee
+    }
+
+    #[test]
+    fn house_rules_change_the_deadline_and_the_length() {
+        let mut game = Game::new(RuleSet {
+            queen_deadline: 2,
+            first_player: Color::White,
+            move_limit: Some(3),
+            ..Default::default()
+        });
+
+        let white_bee = Piece {
+            bug: Bug::Bee,
+            color: Color::White,
+            number: 1,
+        };
+        let white_spider = Piece {
+            bug: Bug::Spider,
+            color: Color::White,
+            number: 1,
+        };
+        let black_spider = Piece {
+            bug: Bug::Spider,
+            color: Color::Black,
+            number: 1,
+        };
+
+        assert_eq!(game.turn(), &Color::White);
+
+        game.put(white_spider.clone(), (0, 0).into()).unwrap(); // white spider is placed at (0, 0)
+        game.put(black_spider.clone(), (1, 0).into()).unwrap(); // black spider is placed at (1, 0)
+
+        assert_eq!(
+            game.put(
+                Piece {
+                    number: 2,
+                    ..white_spider.clone()
+                },
+                (-1, 0).into()
+            ),
+            Err(GameError::QueenMustBePlacedByDeadline)
+        ); // white's second turn is already the deadline
+
+        game.put(white_bee, (-1, 0).into()).unwrap(); // white bee is placed at (-1, 0)
+        game.put(
+            Piece {
+                bug: Bug::Bee,
+                ..black_spider.clone()
+            },
+            (2, 0).into(),
+        )
+        .unwrap(); // black bee is placed at (2, 0)
+        game.put(
+            Piece {
+                number: 2,
+                ..white_spider
+            },
+            (-1, 1).into(),
+        )
+        .unwrap(); // white spider is placed at (-1, 1)
+
+        assert_eq!(game.result(), None);
+
+        game.put(
+            Piece {
+                number: 2,
+                ..black_spider
+            },
+            (3, 0).into(),
+        )
+        .unwrap(); // black spider is placed at (3, 0)
+
+        assert_eq!(
+            game.result(),
+            Some(&GameResult::Draw(DrawReason::MoveLimit))
+        ); // both players made their 3 turns
+    }
+
+    #[test]
+    fn repeating_a_position_three_times_draws() {
+        let mut game = Game::new(RuleSet::default());
+
+        let black_bee = Piece {
+            bug: Bug::Bee,
+            color: Color::Black,
+            number: 1,
+        };
+        let black_ant = Piece {
+            bug: Bug::Ant,
+            ..black_bee.clone()
+        };
+
+        game.put(black_bee.clone(), (0, 0).into()).unwrap(); // black bee is placed at (0, 0)
+        game.put(
+            Piece {
+                color: Color::White,
+                ..black_bee
+            },
+            (1, 0).into(),
+        )
+        .unwrap(); // white bee is placed at (1, 0)
+        game.put(black_ant.clone(), (-1, 0).into()).unwrap(); // black ant is placed at (-1, 0)
+        game.put(
+            Piece {
+                color: Color::White,
+                ..black_ant
+            },
+            (2, 0).into(),
+        )
+        .unwrap(); // white ant is placed at (2, 0)
+
+        // Both ants go back and forth, so the position after the placements comes back twice
+        for _ in 0..2 {
+            assert_eq!(game.result(), None);
+
+            game.move_top((-1, 0).into(), (-1, 1).into()).unwrap();
+            game.move_top((2, 0).into(), (2, -1).into()).unwrap();
+            game.move_top((-1, 1).into(), (-1, 0).into()).unwrap();
+            game.move_top((2, -1).into(), (2, 0).into()).unwrap();
+        }
+
+        assert_eq!(
+            game.result(),
+            Some(&GameResult::Draw(DrawReason::ThreefoldRepetition))
+        );
+
+        game.undo().unwrap();
+        assert_eq!(game.result(), None); // only twice again
+
+        game.redo().unwrap();
+        assert_eq!(
+            game.result(),
+            Some(&GameResult::Draw(DrawReason::ThreefoldRepetition))
+        );
+
+        // UHP games don't end on repetitions, so their ants can shuffle on
+        let mut game = Game::from_game_type("Base").unwrap();
+        for text in [
+            "wS1", "bS1 wS1-", "wQ -wS1", "bQ bS1-", "wA1 -wQ", "bA1 bQ-",
+        ] {
+            let action = notation::parse(&mut game, text).unwrap();
+            game.play(action).unwrap();
+        }
+        for _ in 0..3 {
+            game.move_top((-2, 0).into(), (-2, 1).into()).unwrap();
+            game.move_top((3, 0).into(), (3, -1).into()).unwrap();
+            game.move_top((-2, 1).into(), (-2, 0).into()).unwrap();
+            game.move_top((3, -1).into(), (3, 0).into()).unwrap();
+        }
+        assert_eq!(game.result(), None);
+
+        let game_string = game.to_game_string().unwrap();
+        assert!(Game::from_game_string(&game_string).unwrap() == game);
+    }
+
+    #[test]
+    fn hash_is_the_same_however_the_position_was_reached() {
+        let mut game = Game::new(RuleSet::default());
+        let mut other = Game::new(RuleSet::default());
 
         let black_bee = Piece {
             bug: Bug::Bee,
             color: Color::Black,
+            number: 1,
+        };
+        let black_spider = Piece {
+            bug: Bug::Spider,
+            ..black_bee.clone()
+        };
+        let black_ant = Piece {
+            bug: Bug::Ant,
+            ..black_bee.clone()
         };
+        let [white_bee, white_spider, white_ant] =
+            [&black_bee, &black_spider, &black_ant].map(|p| Piece {
+                color: Color::White,
+                ..p.clone()
+            });
+
+        assert_eq!(game.hash(), other.hash());
+        assert_ne!(
+            game.hash(),
+            Game::new(RuleSet {
+                first_player: Color::White,
+                ..Default::default()
+            })
+            .hash()
+        ); // the same empty board with the other player to move
+
+        for (piece, to) in [
+            (&black_bee, (0, 0)),
+            (&white_bee, (1, 0)),
+            (&black_spider, (-1, 0)),
+            (&white_spider, (2, 0)),
+        ] {
+            game.put(piece.clone(), to.into()).unwrap();
+        }
+        for (piece, to) in [
+            (&black_bee, (0, 0)),
+            (&white_bee, (1, 0)),
+            (&black_ant, (-1, 1)),
+            (&white_ant, (2, -1)),
+        ] {
+            other.put(piece.clone(), to.into()).unwrap();
+        }
+
+        assert_ne!(game.hash(), other.hash()); // different pieces left in the pools
+
+        game.put(black_ant, (-1, 1).into()).unwrap();
+        game.put(white_ant, (2, -1).into()).unwrap();
+        other.put(black_spider, (-1, 0).into()).unwrap();
+        other.put(white_spider, (2, 0).into()).unwrap();
+
+        assert_eq!(game.hash(), other.hash()); // the same placements in another order
+
+        game.undo().unwrap();
+        let mut rehashed = game.clone();
+        rehashed.rehash();
+        assert_eq!(rehashed.hash, game.hash); // kept up to date action by action
+    }
+
+    #[test]
+    fn tournament_opening_keeps_the_bees_off_the_first_turn() {
+        let mut game = Game::new(RuleSet::tournament());
+
         let white_bee = Piece {
             bug: Bug::Bee,
             color: Color::White,
+            number: 1,
         };
-        let black_beetle = Piece {
-            bug: Bug::Beetle,
+        let black_bee = Piece {
+            bug: Bug::Bee,
+            color: Color::Black,
+            number: 1,
+        };
+        let white_spider = Piece {
+            bug: Bug::Spider,
+            color: Color::White,
+            number: 1,
+        };
+
+        assert_eq!(
+            game.put(white_bee.clone(), (0, 0).into()),
+            Err(GameError::QueenPlacedOnFirstTurn)
+        ); // white moves first, but not with the bee
+        assert!(!game.can_place(&white_bee));
+        assert!(game
+            .legal_actions()
+            .iter()
+            .all(|a| !matches!(a, Action::Put { piece, .. } if piece == &white_bee)));
+
+        game.put(white_spider, (0, 0).into()).unwrap(); // white spider is placed at (0, 0)
+
+        assert_eq!(
+            game.put(black_bee.clone(), (1, 0).into()),
+            Err(GameError::QueenPlacedOnFirstTurn)
+        ); // black's first turn too
+
+        game.put(
+            Piece {
+                bug: Bug::Ant,
+                ..black_bee
+            },
+            (1, 0).into(),
+        )
+        .unwrap(); // black ant is placed at (1, 0)
+
+        assert!(game.can_place(&white_bee)); // white's second turn
+        game.put(white_bee, (-1, 0).into()).unwrap();
+    }
+
+    #[test]
+    fn pieces_are_placed_in_order() {
+        let mut game = Game::new(RuleSet::default());
+
+        let black_spider = Piece {
+            bug: Bug::Spider,
+            color: Color::Black,
+            number: 1,
+        };
+
+        assert_eq!(
+            game.put(
+                Piece {
+                    number: 2,
+                    ..black_spider.clone()
+                },
+                (0, 0).into()
+            ),
+            Err(GameError::PlacedOutOfOrder)
+        );
+        assert!(game.placeable_pieces(&Color::Black).contains(&black_spider));
+
+        game.put(black_spider.clone(), (0, 0).into()).unwrap();
+
+        assert!(game.placeable_pieces(&Color::Black).contains(&Piece {
+            number: 2,
+            ..black_spider
+        }));
+        assert_eq!(game.placeable_pieces(&Color::Black).len(), 5); // one of each bug
+    }
+
+    #[test]
+    fn undo_and_redo_restore_the_game() {
+        let black_bee = Piece {
+            bug: Bug::Bee,
             color: Color::Black,
+            number: 1,
+        };
+        let white_bee = Piece {
+            bug: Bug::Bee,
+            color: Color::White,
+            number: 1,
         };
         let white_beetle = Piece {
             bug: Bug::Beetle,
             color: Color::White,
+            number: 1,
         };
-        let black_ant = Piece {
-            bug: Bug::Ant,
+
+        let mut game = with_pool(vec![
+            white_beetle.clone(),
+            black_bee.clone(),
+            white_bee.clone(),
+        ]);
+
+        let same_position = |a: &Game, b: &Game| {
+            a.board == b.board
+                && a.pool == b.pool
+                && a.turn == b.turn
+                && a.turn_number == b.turn_number
+                && a.result == b.result
+        };
+
+        let mut positions = vec![game.clone()];
+        for action in [
+            Action::Put {
+                piece: black_bee.clone(),
+                to: (0, 0).into(),
+            },
+            Action::Put {
+                piece: white_bee.clone(),
+                to: (1, 0).into(),
+            },
+            Action::Move {
+                piece: black_bee.clone(),
+                from: (0, 0).into(),
+                to: (0, 1).into(),
+            },
+            Action::Put {
+                piece: white_beetle.clone(),
+                to: (2, 0).into(),
+            },
+            Action::Move {
+                piece: black_bee.clone(),
+                from: (0, 1).into(),
+                to: (1, 1).into(),
+            },
+            Action::Move {
+                piece: white_beetle.clone(),
+                from: (2, 0).into(),
+                to: (1, 1).into(),
+            }, // black is forced to pass after this one
+        ] {
+            game.play(action).unwrap();
+            positions.push(game.clone());
+        }
+
+        for expected in positions.iter().rev().skip(1) {
+            game.undo().unwrap();
+            assert!(same_position(&game, expected));
+        }
+        assert_eq!(game.undo(), Err(GameError::NothingToUndo));
+
+        for expected in positions.iter().skip(1) {
+            game.redo().unwrap();
+            assert!(same_position(&game, expected));
+        }
+        assert_eq!(game.redo(), Err(GameError::NothingToRedo));
+        assert_eq!(game.history().last(), Some(&Action::Pass));
+
+        game.undo().unwrap();
+        game.move_top((2, 0).into(), (2, -1).into()).unwrap(); // a new action forgets the undone ones
+        assert_eq!(game.redo(), Err(GameError::NothingToRedo));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn games_survive_a_round_trip() {
+        let mut game = Game::new(RuleSet::default());
+
+        let black_bee = Piece {
+            bug: Bug::Bee,
             color: Color::Black,
+            number: 1,
+        };
+        let black_beetle = Piece {
+            bug: Bug::Beetle,
+            color: Color::Black,
+            number: 1,
+        };
+        let white_bee = Piece {
+            bug: Bug::Bee,
+            color: Color::White,
+            number: 1,
         };
         let white_ant = Piece {
             bug: Bug::Ant,
             color: Color::White,
+            number: 1,
+        };
+
+        game.put(black_bee, (0, 0).into()).unwrap();
+        game.put(white_bee, (1, 0).into()).unwrap();
+        game.put(black_beetle, (-1, 1).into()).unwrap();
+        game.put(white_ant.clone(), (2, 0).into()).unwrap();
+        game.move_top((-1, 1).into(), (0, 0).into()).unwrap(); // the beetle stacks on its bee
+        game.put(
+            Piece {
+                number: 2,
+                ..white_ant
+            },
+            (3, 0).into(),
+        )
+        .unwrap();
+        game.undo().unwrap();
+
+        let json = serde_json::to_string(&game).unwrap();
+        let restored: Game = serde_json::from_str(&json).unwrap();
+
+        assert!(restored == game);
+        assert_eq!(restored.board.height((0, 0).into()), 2);
+        assert_eq!(restored.undone, game.undone);
+    }
+
+    #[test]
+    fn perft_matches_the_reference_counts() {
+        // Published by Mzinga, where nobody places their bee on their first turn
+        let mut game = Game::new(RuleSet::tournament());
+        for (depth, leaves) in [1, 4, 96, 1440, 21600].into_iter().enumerate() {
+            assert_eq!(game.perft(depth as u8), Ok(leaves));
+        }
+
+        let mut game = Game::new(RuleSet {
+            expansions: EXPANSIONS.to_vec(),
+            ..RuleSet::tournament()
+        });
+        for (depth, leaves) in [1, 7, 294, 6678].into_iter().enumerate() {
+            assert_eq!(game.perft(depth as u8), Ok(leaves));
+        }
+    }
+
+    #[test]
+    fn perft_keeps_the_mid_game_baselines() {
+        // Regression baselines, not reference counts: this engine counted them once it matched the published ones
+        // They only tell that the moves of these positions didn't change, not that they are right
+        for (game_string, counts) in [
+            (WIN_IN_ONE, [1, 85, 8141]),
+            (
+                r"Base+MLP;InProgress;White[6];wS1;bP -wS1;wQ wS1\;bM \bP;wA1 wQ-;bQ /bP;wA1 -wQ;bM -bP;wQ wS1-;wA1 \wS1",
+                [1, 51, 1977],
+            ),
+        ] {
+            let mut game = Game::from_game_string(game_string).unwrap();
+            for (depth, leaves) in counts.into_iter().enumerate() {
+                assert_eq!(game.perft(depth as u8), Ok(leaves));
+            }
+
+            let divided = game.perft_divide(2).unwrap();
+            assert_eq!(divided.len() as u64, counts[1]); // one entry per legal action
+            assert_eq!(
+                divided.iter().map(|(_, leaves)| leaves).sum::<u64>(),
+                counts[2]
+            );
+            assert_eq!(game.to_game_string(), Ok(game_string.to_string())); // counting leaves the game as it was
+        }
+    }
+
+    #[test]
+    #[ignore] // too slow without optimizations, run with --release --ignored
+    fn perft_matches_the_deeper_reference_counts() {
+        assert_eq!(Game::new(RuleSet::tournament()).perft(5), Ok(516240));
+
+        let mut game = Game::new(RuleSet {
+            expansions: EXPANSIONS.to_vec(),
+            ..RuleSet::tournament()
+        });
+        assert_eq!(game.perft(4), Ok(151686));
+    }
+
+    #[test]
+    fn game_strings_replay_the_moves() {
+        let game_string = "Base+M;InProgress;White[4];wS1;bM wS1-;wQ -wS1;bQ bM\\;wA1 -wQ;bQ bM-";
+        let game = Game::from_game_string(game_string).unwrap();
+
+        assert_eq!(game.to_game_string(), Ok(game_string.to_string()));
+        assert_eq!(game.turn(), &Color::White);
+        assert_eq!(
+            Game::from_game_type("Base+MLP").unwrap().to_game_string(),
+            Ok("Base+MLP;NotStarted;White[1]".to_string())
+        );
+    }
+
+    #[test]
+    fn game_strings_leave_the_other_rules_apart() {
+        let mut game = Game::new(RuleSet::default());
+        for text in ["bS1", "wQ bS1-", "bQ -bS1"] {
+            let action = notation::parse(&mut game, text).unwrap();
+            game.play(action).unwrap();
+        }
+
+        let game_string = game.to_game_string().unwrap();
+        assert_eq!(game_string, "Base;InProgress;White[2];bS1;wQ bS1-;bQ -bS1"); // the GameTypeString stays the standard one
+        assert!(
+            Game::from_game_string_with_rules(&game_string, RuleSet::default()).unwrap() == game
+        );
+        assert_eq!(
+            Game::from_game_string(&game_string).err(),
+            Some(GameStringError::IllegalMove {
+                index: 0,
+                error: GameError::NotYourTurn
+            })
+        ); // white moves first in UHP games
+
+        let rules = RuleSet {
+            expansions: vec![Bug::Ladybug],
+            queen_deadline: 3,
+            move_limit: Some(20),
+            ..Default::default()
+        };
+        let mut game = Game::new(rules.clone());
+        for text in ["bL", "wS1 bL-"] {
+            let action = notation::parse(&mut game, text).unwrap();
+            game.play(action).unwrap();
+        }
+
+        let game_string = game.to_game_string().unwrap();
+        assert_eq!(game_string, "Base+L;InProgress;Black[2];bL;wS1 bL-");
+        assert!(
+            Game::from_game_string_with_rules(
+                &game_string,
+                RuleSet {
+                    expansions: vec![],
+                    ..rules
+                }
+            )
+            .unwrap()
+                == game
+        ); // the expansions come from the GameString
+
+        assert_eq!(
+            Game::new(RuleSet {
+                expansions: vec![Bug::Custom('D')],
+                ..Default::default()
+            })
+            .to_game_string(),
+            Err(GameStringError::HomebrewBug(Bug::Custom('D')))
+        );
+        assert_eq!(
+            Game::from_game_type("Base+M first=black").err(),
+            Some(GameStringError::UnknownGameType(
+                "Base+M first=black".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn game_strings_tell_the_first_illegal_move() {
+        assert_eq!(
+            Game::from_game_string("Base;InProgress;White[2];wS1;bS1 wS1-;wQ bS1-").err(),
+            Some(GameStringError::IllegalMove {
+                index: 2,
+                error: GameError::SpawnedInOpponentsHive
+            })
+        );
+        assert_eq!(
+            Game::from_game_string("Base;InProgress;White[2];wS1;pass").err(),
+            Some(GameStringError::IllegalMove {
+                index: 1,
+                error: GameError::InvalidMove
+            })
+        ); // black could place a piece
+        assert!(matches!(
+            Game::from_game_string("Base;InProgress;White[2];wS1;bX1 wS1-").err(),
+            Some(GameStringError::UnreadableMove { index: 1, .. })
+        ));
+        assert_eq!(
+            Game::from_game_string("Base;InProgress;White[1];wS1").err(),
+            Some(GameStringError::TurnMismatch("White[1]".to_string()))
+        );
+        assert_eq!(
+            Game::from_game_string("Base+X;NotStarted;White[1]").err(),
+            Some(GameStringError::UnknownGameType("Base+X".to_string()))
+        );
+    }
+
+    #[test]
+    fn mosquito_copies_touched_bugs() {
+        let mut game = Game::new(RuleSet {
+            expansions: vec![Bug::Mosquito],
+            ..Default::default()
+        });
+
+        let black_mosquito = Piece {
+            bug: Bug::Mosquito,
+            color: Color::Black,
+            number: 1,
+        };
+        let white_mosquito = Piece {
+            bug: Bug::Mosquito,
+            color: Color::White,
+            number: 1,
         };
         let white_grasshopper = Piece {
             bug: Bug::Grasshopper,
             color: Color::White,
+            number: 1,
+        };
+        let black_bee = Piece {
+            bug: Bug::Bee,
+            color: Color::Black,
+            number: 1,
+        };
+        let white_ant = Piece {
+            bug: Bug::Ant,
+            color: Color::White,
+            number: 1,
         };
 
-        game.put(black_bee.clone(), (0, 0).into()).unwrap(); // black bee is placed at (0, 0)
-        game.put(white_bee.clone(), (1, 0).into()).unwrap(); // white bee is placed at (1, 0)
-        game.put(black_beetle.clone(), (-1, 1).into()).unwrap(); // black beetle is placed at (-1, 1)
-        game.put(white_beetle.clone(), (2, 0).into()).unwrap(); // white beetle is placed at (2, 0)
-        game.put(black_ant.clone(), (0, -1).into()).unwrap(); // black ant is placed at (0, -1)
-        game.put(white_grasshopper.clone(), (1, 1).into()).unwrap(); // white grasshopper is placed at (1, 1)
+        game.board.put_piece(black_mosquito.clone(), (0, 0).into());
+        game.board.put_piece(white_grasshopper, (1, 0).into());
+        game.board.put_piece(black_bee.clone(), (-1, 0).into());
 
-        game.move_top((-1, 1).into(), (0, 1).into()).unwrap(); // black beetle moves to (0, 1)
+        assert_eq!(game.possible_moves((0, 0).into()), Ok(HashSet::new())); // it holds the grasshopper and the bee together
+        assert_eq!(
+            game.bugs
+                .possible_moves(&Bug::Mosquito, &mut game.board, (0, 0).into()),
+            Ok(HashSet::from([
+                (2, 0).into(),  // jumping like the grasshopper
+                (-2, 0).into(), // jumping like the grasshopper over the bee
+                (-1, 1).into(), // sliding like the bee
+                (0, 1).into(),  // sliding like the bee
+                (1, -1).into(), // sliding like the bee
+                (0, -1).into(), // sliding like the bee
+            ]))
+        );
 
-        game.put(white_ant.clone(), (2, -1).into()).unwrap(); // white ant is placed at (2, -1)
+        let mut game = Game::new(RuleSet {
+            expansions: vec![Bug::Mosquito],
+            ..Default::default()
+        });
+        game.board.put_piece(black_mosquito.clone(), (0, 0).into());
+        game.board.put_piece(white_mosquito, (1, 0).into());
 
-        game.move_top((0, -1).into(), (1, -1).into()).unwrap(); // black ant moves to (1, -1)
+        assert_eq!(game.possible_moves((0, 0).into()), Ok(HashSet::new())); // touching only a mosquito
+
+        let mut game = Game::new(RuleSet {
+            expansions: vec![Bug::Mosquito],
+            ..Default::default()
+        });
+        game.board.put_piece(black_bee, (0, 0).into());
+        game.board.put_piece(white_ant, (1, 0).into());
+        game.board.put_piece(black_mosquito, (1, 0).into());
+
+        let moves = game.possible_moves((1, 0).into()).unwrap();
+        assert_eq!(moves.len(), 6); // on top of the hive it moves like a beetle
+        assert!(moves.contains(&(0, 0).into()));
+    }
+
+    #[test]
+    fn ladybug_walks_over_the_hive() {
+        let mut game = Game::new(RuleSet {
+            expansions: vec![Bug::Ladybug],
+            ..Default::default()
+        });
+
+        let black_ladybug = Piece {
+            bug: Bug::Ladybug,
+            color: Color::Black,
+            number: 1,
+        };
+        let white_bee = Piece {
+            bug: Bug::Bee,
+            color: Color::White,
+            number: 1,
+        };
+        let white_ant = Piece {
+            bug: Bug::Ant,
+            color: Color::White,
+            number: 1,
+        };
+
+        game.board.put_piece(black_ladybug.clone(), (0, 0).into());
+        game.board.put_piece(white_bee, (1, 0).into());
+        game.board.put_piece(white_ant, (2, 0).into());
+
+        let before = game.board.clone();
 
         assert_eq!(
-            game.move_top((1, 1).into(), (0, 1).into()),
-            Err(GameError::GameFinished(GameResult::Win(Color::Black)))
-        ); // white grasshopper cannot move to (0, 1) because the black bee is trapped
+            game.possible_moves((0, 0).into()),
+            Ok(HashSet::from([
+                (1, 1).into(),
+                (2, 1).into(),
+                (3, 0).into(),
+                (3, -1).into(),
+                (2, -1).into(),
+            ]))
+        ); // over the bee, over the ant and down around the ant
+        assert!(game.board == before);
+    }
+
+    #[test]
+    fn pillbug_throws_pieces_around() {
+        let mut game = with_pool(vec![]);
+
+        let black_bee = Piece {
+            bug: Bug::Bee,
+            color: Color::Black,
+            number: 1,
+        };
+        let black_pillbug = Piece {
+            bug: Bug::Pillbug,
+            color: Color::Black,
+            number: 1,
+        };
+        let black_mosquito = Piece {
+            bug: Bug::Mosquito,
+            color: Color::Black,
+            number: 1,
+        };
+        let white_bee = Piece {
+            bug: Bug::Bee,
+            color: Color::White,
+            number: 1,
+        };
+        let white_ant = Piece {
+            bug: Bug::Ant,
+            color: Color::White,
+            number: 1,
+        };
+
+        game.board.put_piece(white_bee.clone(), (-1, 0).into());
+        game.board.put_piece(black_bee.clone(), (0, 0).into());
+        game.board.put_piece(black_pillbug.clone(), (1, 0).into());
+        game.board.put_piece(white_ant.clone(), (2, 0).into());
+
+        assert_eq!(
+            game.throw((0, 0).into(), (1, 0).into(), (0, 1).into()),
+            Err(GameError::NotAPillbug)
+        );
+        assert_eq!(
+            game.throw((1, 0).into(), (0, 0).into(), (1, -1).into()),
+            Err(GameError::HiveDisconnected)
+        ); // the black bee holds the white bee to the hive
+        assert_eq!(
+            game.throw((1, 0).into(), (2, 0).into(), (3, 0).into()),
+            Err(GameError::InvalidMove)
+        ); // (3, 0) is not around the pillbug
+
+        assert!(game.legal_actions().contains(&Action::Throw {
+            piece: white_ant.clone(),
+            pillbug: (1, 0).into(),
+            from: (2, 0).into(),
+            to: (1, -1).into(),
+        }));
+
+        game.throw((1, 0).into(), (2, 0).into(), (1, -1).into())
+            .unwrap(); // the white ant is thrown over the pillbug
+
+        assert_eq!(
+            game.move_top((1, -1).into(), (2, 0).into()),
+            Err(GameError::PieceJustMoved)
+        ); // white can't move the ant right after it was thrown
+
+        game.move_top((-1, 0).into(), (-1, 1).into()).unwrap(); // white bee moves to (-1, 1)
+
+        game.board.put_piece(black_mosquito, (1, 1).into());
+
+        assert!(game.can_throw((1, 1).into())); // the mosquito touches the pillbug
+        assert!(!game.can_throw((0, 0).into()));
+
+        game.undo().unwrap();
+        game.undo().unwrap();
+
+        assert_eq!(game.get_top_piece((2, 0).into()), Some(&white_ant));
     }
 }
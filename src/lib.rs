@@ -0,0 +1,15 @@
+pub mod board;
+pub mod boardspace;
+pub mod bugs;
+pub mod coordinate;
+pub mod engine;
+pub mod evaluation;
+pub mod game;
+pub mod mcts;
+pub mod notation;
+pub mod piece;
+pub mod rng;
+pub mod rules;
+#[cfg(test)]
+mod test_positions;
+pub mod uhp;
@@ -1,27 +1,43 @@
-mod board;
-mod coordinate;
-mod game;
-mod piece;
-
 use std::collections::HashSet;
 
 use yew::prelude::*;
 use yew::{html, Component, Context, Html};
 
-use crate::coordinate::XYCoordinate;
+use hive_rust::coordinate::XYCoordinate;
+use hive_rust::engine::{AlphaBeta, Evaluator, Limit, Player};
+use hive_rust::evaluation::Heuristic;
+use hive_rust::rules::RuleSet;
+use hive_rust::{game, notation, piece};
 // Define the possible messages which can be sent to the component
 #[derive(Debug, Clone, PartialEq)]
 enum Msg {
     Coordinate((i8, i8)),
     Piece(piece::Piece), // TODO: this should be a reference
+    Undo,
+    Redo,
+    Expansion(piece::Bug),
+    Tournament,
+    Throw,
+    ComputerMove,
+}
+
+// What was picked to be played next: a pool piece to place or the cell of a piece to move
+#[derive(Debug, Clone, PartialEq)]
+enum Selection {
+    Coordinate((i8, i8)),
+    Piece(piece::Piece),
 }
 
 #[derive(PartialEq, Clone)]
 struct App {
-    selected: Option<Msg>,
+    selected: Option<Selection>,
     possible_moves: HashSet<XYCoordinate>,
     game: game::Game,
     game_error: String,
+    expansions: Vec<piece::Bug>,
+    tournament: bool,
+    // Pillbug chosen to throw the next selected piece
+    pillbug: Option<(i8, i8)>,
 }
 
 impl Component for App {
@@ -31,69 +47,113 @@ impl Component for App {
     fn create(_ctx: &Context<Self>) -> Self {
         Self {
             selected: None,
-            game: game::Game::new(game::Game::default_pool()),
+            game: game::Game::new(RuleSet::default()),
             game_error: "".to_string(),
             possible_moves: HashSet::new(),
+            expansions: vec![],
+            tournament: false,
+            pillbug: None,
         }
     }
 
     fn update(&mut self, _ctx: &Context<Self>, msg: Self::Message) -> bool {
         self.game_error = "".to_string();
 
-        let selected_clone = self.selected.clone();
+        // Picking anything but a cell cancels a throw
+        if !matches!(msg, Msg::Coordinate(_)) {
+            self.pillbug = None;
+        }
 
         match (msg.clone(), self.selected.clone()) {
-            (Msg::Coordinate(pos), Some(Msg::Piece(p))) => {
+            (Msg::Undo, _) => {
+                if let Err(e) = self.game.undo() {
+                    self.game_error = e.to_string();
+                }
+                self.selected = None;
+            }
+            (Msg::Redo, _) => {
+                if let Err(e) = self.game.redo() {
+                    self.game_error = e.to_string();
+                }
+                self.selected = None;
+            }
+            (Msg::Expansion(bug), _) => {
+                // Changing the expansions starts a new game
+                if let Some(index) = self.expansions.iter().position(|b| b == &bug) {
+                    self.expansions.remove(index);
+                } else {
+                    self.expansions.push(bug);
+                }
+                self.game = game::Game::new(rules(&self.expansions, self.tournament));
+                self.selected = None;
+            }
+            (Msg::Tournament, _) => {
+                // Changing the rules starts a new game too
+                self.tournament = !self.tournament;
+                self.game = game::Game::new(rules(&self.expansions, self.tournament));
+                self.selected = None;
+            }
+            (Msg::Throw, Some(Selection::Coordinate(pillbug))) => {
+                // The next selected piece will be thrown by this pillbug
+                self.pillbug = Some(pillbug);
+                self.selected = None;
+            }
+            (Msg::Throw, _) => {
+                self.game_error = "Select the pillbug to throw with first".to_string();
+            }
+            (Msg::ComputerMove, _) => {
+                self.game_error = computer_move(&mut self.game);
+                self.selected = None;
+            }
+            (Msg::Coordinate(pos), Some(Selection::Piece(p))) => {
                 match self.game.put(p, pos.into()) {
                     Ok(_) => {}
-                    Err(e) => self.game_error = format!("{:?}", e),
+                    Err(e) => self.game_error = e.to_string(),
                 }
                 self.selected = None;
             }
             (Msg::Piece(p), None) => {
-                self.selected = Some(Msg::Piece(p));
+                self.selected = Some(Selection::Piece(p));
             }
             (Msg::Coordinate(from), None) => {
                 if self.game.get_top_piece(from.into()).is_some() {
-                    self.selected = Some(Msg::Coordinate(from));
+                    self.selected = Some(Selection::Coordinate(from));
                 } else {
                     self.game_error = "No piece selected".to_string();
                 }
             }
-            (Msg::Piece(p), Some(Msg::Coordinate(_))) => {
-                self.selected = Some(Msg::Piece(p)); // we flush the selected position
+            (Msg::Piece(p), Some(Selection::Coordinate(_))) => {
+                self.selected = Some(Selection::Piece(p)); // we flush the selected position
             }
-            (Msg::Coordinate(to), Some(Msg::Coordinate(from))) => {
+            (Msg::Coordinate(to), Some(Selection::Coordinate(from))) => {
                 if from == to {
                     self.selected = None;
+                    self.pillbug = None;
                 } else {
-                    match self.game.move_top(from.into(), to.into()) {
+                    let result = match self.pillbug.take() {
+                        Some(pillbug) => self.game.throw(pillbug.into(), from.into(), to.into()),
+                        None => self.game.move_top(from.into(), to.into()),
+                    };
+                    match result {
                         Ok(_) => {}
-                        Err(e) => self.game_error = format!("{:?}", e),
+                        Err(e) => self.game_error = e.to_string(),
                     }
                     self.selected = None;
                 }
             }
-            (Msg::Piece(p), Some(Msg::Piece(old))) => {
+            (Msg::Piece(p), Some(Selection::Piece(old))) => {
                 if old == p {
                     self.selected = None;
                 } else {
-                    self.selected = Some(Msg::Piece(p)); // we flush the selected piece
+                    self.selected = Some(Selection::Piece(p)); // we flush the selected piece
                 }
             }
         }
 
-        match (msg.clone(), selected_clone) {
-            (Msg::Coordinate(from), None) => match self.game.possible_moves(from.into()) {
-                Ok(moves) => {
-                    self.possible_moves = moves;
-                }
-                Err(_) => {} // TODO: how should we handle this error?
-            },
-            _ => {
-                self.possible_moves = HashSet::new();
-            }
-        }
+        self.possible_moves = match &self.selected {
+            Some(selected) => legal_destinations(&mut self.game, selected, self.pillbug),
+            None => HashSet::new(),
+        };
 
         true // Return true to cause the displayed change to update
     }
@@ -156,9 +216,9 @@ impl Component for App {
             </div>
         };
 
-        let pool = self.game.get_pool();
-        let whites = pool.iter().filter(|p| p.color == piece::Color::White);
-        let blacks = pool.iter().filter(|p| p.color == piece::Color::Black);
+        // Only the next piece of each bug can be placed
+        let whites = self.game.placeable_pieces(&piece::Color::White);
+        let blacks = self.game.placeable_pieces(&piece::Color::Black);
 
         html! {
             <div>
@@ -166,22 +226,62 @@ impl Component for App {
                 {board}
                 <p>
                 {
-                    if let Some(pos) = &self.selected {
-                        format!("Selected: {:?}", pos)
+                    if let Some(selected) = &self.selected {
+                        format!("Selected: {}", selection_name(&self.game, selected))
                     } else {
                         "No selection".to_string()
                     }
                 }
                 </p>
+                <p>
+                {
+                    if let Some(game::Action::Pass) = self.game.history().last() {
+                        format!("{} had no legal actions and passed", !self.game.turn().clone())
+                    } else {
+                        "".to_string()
+                    }
+                }
+                </p>
+                <p>{ format!("Moves: {}", notation::history(&self.game).join(", ")) }</p>
+                <p>{ evaluation(&self.game) }</p>
+                <button class="button" onclick={ctx.link().callback(|_| Msg::Undo)}>{ "Undo" }</button>
+                <button class="button" onclick={ctx.link().callback(|_| Msg::Redo)}>{ "Redo" }</button>
+                <button class="button" onclick={ctx.link().callback(|_| Msg::Throw)}>{ "Throw with pillbug" }</button>
+                <button class="button" onclick={ctx.link().callback(|_| Msg::ComputerMove)}>{ "Computer move" }</button>
+                <p>
+                {
+                    if let Some(pillbug) = self.pillbug {
+                        format!("Throwing with {}", selection_name(&self.game, &Selection::Coordinate(pillbug)))
+                    } else {
+                        "".to_string()
+                    }
+                }
+                </p>
+                {
+                    for piece::EXPANSIONS.iter().map(|bug| {
+                        let enabled = self.expansions.contains(bug);
+                        html! {
+                            <button class="button" onclick={
+                                let bug = bug.clone();
+                                ctx.link().callback(move |_| Msg::Expansion(bug.clone()))
+                            }>
+                            { format!("{} {}", bug, if enabled { "on" } else { "off" }) }
+                            </button>
+                        }
+                    })
+                }
+                <button class="button" onclick={ctx.link().callback(|_| Msg::Tournament)}>
+                { format!("Tournament rules {}", if self.tournament { "on" } else { "off" }) }
+                </button>
 
             <div class="container">
                 <div class="row">
 
                 <div class="col">
                 {
-                    for blacks.map(|piece|{
+                    for blacks.iter().map(|piece|{
                             html!{
-                                <button class="button" onclick={
+                                <button class="button" disabled={greyed_out(&self.game, piece)} onclick={
                                     let piece = piece.clone(); // TODO: what is the right way to do this?
                                     ctx.link().callback(move |_| Msg::Piece(piece.clone()))
                                 }>
@@ -195,9 +295,9 @@ impl Component for App {
 
                 <div class="col">
                 {
-                    for whites.map(|piece|{
+                    for whites.iter().map(|piece|{
                             html!{
-                                <button class="button" onclick={
+                                <button class="button" disabled={greyed_out(&self.game, piece)} onclick={
                                     let piece = piece.clone(); // TODO: what is the right way to do this?
                                     ctx.link().callback(move |_| Msg::Piece(piece.clone()))
                                 }>
@@ -225,76 +325,199 @@ impl Component for App {
 fn update(mut app: App, msg: Msg) -> App {
     app.game_error = "".to_string();
 
-    let selected_clone = app.selected.clone();
+    // Picking anything but a cell cancels a throw
+    if !matches!(msg, Msg::Coordinate(_)) {
+        app.pillbug = None;
+    }
 
     match (msg.clone(), app.selected.clone()) {
-        (Msg::Coordinate(pos), Some(Msg::Piece(p))) => {
+        (Msg::Undo, _) => {
+            if let Err(e) = app.game.undo() {
+                app.game_error = e.to_string();
+            }
+            app.selected = None;
+        }
+        (Msg::Redo, _) => {
+            if let Err(e) = app.game.redo() {
+                app.game_error = e.to_string();
+            }
+            app.selected = None;
+        }
+        (Msg::Expansion(bug), _) => {
+            // Changing the expansions starts a new game
+            if let Some(index) = app.expansions.iter().position(|b| b == &bug) {
+                app.expansions.remove(index);
+            } else {
+                app.expansions.push(bug);
+            }
+            app.game = game::Game::new(rules(&app.expansions, app.tournament));
+            app.selected = None;
+        }
+        (Msg::Tournament, _) => {
+            // Changing the rules starts a new game too
+            app.tournament = !app.tournament;
+            app.game = game::Game::new(rules(&app.expansions, app.tournament));
+            app.selected = None;
+        }
+        (Msg::Throw, Some(Selection::Coordinate(pillbug))) => {
+            // The next selected piece will be thrown by this pillbug
+            app.pillbug = Some(pillbug);
+            app.selected = None;
+        }
+        (Msg::Throw, _) => {
+            app.game_error = "Select the pillbug to throw with first".to_string();
+        }
+        (Msg::ComputerMove, _) => {
+            app.game_error = computer_move(&mut app.game);
+            app.selected = None;
+        }
+        (Msg::Coordinate(pos), Some(Selection::Piece(p))) => {
             match app.game.put(p, pos.into()) {
                 Ok(_) => {}
-                Err(e) => app.game_error = format!("{:?}", e),
+                Err(e) => app.game_error = e.to_string(),
             }
             app.selected = None;
         }
         (Msg::Piece(p), None) => {
-            app.selected = Some(Msg::Piece(p));
+            app.selected = Some(Selection::Piece(p));
         }
         (Msg::Coordinate(from), None) => {
             if app.game.get_top_piece(from.into()).is_some() {
-                app.selected = Some(Msg::Coordinate(from));
+                app.selected = Some(Selection::Coordinate(from));
             } else {
                 app.game_error = "No piece selected".to_string();
             }
         }
-        (Msg::Piece(p), Some(Msg::Coordinate(_))) => {
-            app.selected = Some(Msg::Piece(p)); // we flush the selected position
+        (Msg::Piece(p), Some(Selection::Coordinate(_))) => {
+            app.selected = Some(Selection::Piece(p)); // we flush the selected position
         }
-        (Msg::Coordinate(to), Some(Msg::Coordinate(from))) => {
+        (Msg::Coordinate(to), Some(Selection::Coordinate(from))) => {
             if from == to {
                 app.selected = None;
+                app.pillbug = None;
             } else {
-                match app.game.move_top(from.into(), to.into()) {
+                let result = match app.pillbug.take() {
+                    Some(pillbug) => app.game.throw(pillbug.into(), from.into(), to.into()),
+                    None => app.game.move_top(from.into(), to.into()),
+                };
+                match result {
                     Ok(_) => {}
-                    Err(e) => app.game_error = format!("{:?}", e),
+                    Err(e) => app.game_error = e.to_string(),
                 }
                 app.selected = None;
             }
         }
-        (Msg::Piece(p), Some(Msg::Piece(old))) => {
+        (Msg::Piece(p), Some(Selection::Piece(old))) => {
             if old == p {
                 app.selected = None;
             } else {
-                app.selected = Some(Msg::Piece(p)); // we flush the selected piece
+                app.selected = Some(Selection::Piece(p)); // we flush the selected piece
             }
         }
     }
 
-    match (msg.clone(), selected_clone) {
-        (Msg::Coordinate(from), None) => match app.game.possible_moves(from.into()) {
-            Ok(moves) => {
-                app.possible_moves = moves;
-            }
-            Err(_) => {} // TODO: how should we handle this error?
-        },
-        _ => {
-            app.possible_moves = HashSet::new();
-        }
-    }
+    app.possible_moves = match &app.selected {
+        Some(selected) => legal_destinations(&mut app.game, selected, app.pillbug),
+        None => HashSet::new(),
+    };
 
     app
 }
 
+// Lets the computer play for the player to move, telling why it couldn't
+fn computer_move(game: &mut game::Game) -> String {
+    // Searching by time needs a clock, which the browser doesn't give
+    let mut player = AlphaBeta::new(Heuristic::default(), Limit::Depth(2));
+
+    match player.choose(game).map(|action| game.play(action)) {
+        Some(Ok(_)) => "".to_string(),
+        Some(Err(e)) => e.to_string(),
+        None => "The game is finished".to_string(),
+    }
+}
+
+// How the position looks for the player to move, e.g. "Evaluation for White: 12"
+fn evaluation(game: &game::Game) -> String {
+    let score = Heuristic::default().evaluate(&mut game.clone());
+    format!("Evaluation for {}: {}", game.turn(), score)
+}
+
+// The official tournament rules or the default ones, with the chosen expansions
+fn rules(expansions: &[piece::Bug], tournament: bool) -> RuleSet {
+    let rules = match tournament {
+        true => RuleSet::tournament(),
+        false => RuleSet::default(),
+    };
+    RuleSet {
+        expansions: expansions.to_vec(),
+        ..rules
+    }
+}
+
+// Whether the pool button of the piece is greyed out, when it's the piece's turn but it can't be placed
+fn greyed_out(game: &game::Game, piece: &piece::Piece) -> bool {
+    &piece.color == game.turn() && !game.can_place(piece)
+}
+
+// How the selection is written in Hive notation, e.g. "wA1"
+fn selection_name(game: &game::Game, selected: &Selection) -> String {
+    match selected {
+        Selection::Coordinate(pos) => game
+            .get_top_piece((*pos).into())
+            .map(notation::name)
+            .unwrap_or(format!("{:?}", pos)),
+        Selection::Piece(piece) => notation::name(piece),
+    }
+}
+
+// Cells where the selected piece can be placed, moved or thrown to
+fn legal_destinations(
+    game: &mut game::Game,
+    selected: &Selection,
+    pillbug: Option<(i8, i8)>,
+) -> HashSet<XYCoordinate> {
+    game.legal_actions()
+        .into_iter()
+        .filter_map(|action| match (action, selected) {
+            (game::Action::Put { piece, to }, Selection::Piece(selected)) if &piece == selected => {
+                Some(to)
+            }
+            (game::Action::Move { from, to, .. }, Selection::Coordinate(selected))
+                if pillbug.is_none() && from == (*selected).into() =>
+            {
+                Some(to)
+            }
+            (
+                game::Action::Throw {
+                    pillbug: thrower,
+                    from,
+                    to,
+                    ..
+                },
+                Selection::Coordinate(selected),
+            ) if pillbug.map(XYCoordinate::from) == Some(thrower) && from == (*selected).into() => {
+                Some(to)
+            }
+            _ => None,
+        })
+        .collect()
+}
+
 #[function_component]
 fn FnApp() -> Html {
     // TODO: there's a weird thing happening when from_row is odd
 
     let state = use_state_eq(|| App {
         selected: None,
-        game: game::Game::new(game::Game::default_pool()),
+        game: game::Game::new(RuleSet::default()),
         game_error: "".to_string(),
         possible_moves: HashSet::new(),
+        expansions: vec![],
+        tournament: false,
+        pillbug: None,
     });
 
-    let from_row = (*state)
+    let from_row = state
         .game
         .hive()
         .iter()
@@ -302,7 +525,7 @@ fn FnApp() -> Html {
         .min()
         .unwrap_or(0);
 
-    let to_row = (*state)
+    let to_row = state
         .game
         .hive()
         .iter()
@@ -310,7 +533,7 @@ fn FnApp() -> Html {
         .max()
         .unwrap_or(0);
 
-    let from_column = (*state)
+    let from_column = state
         .game
         .hive()
         .iter()
@@ -319,7 +542,7 @@ fn FnApp() -> Html {
         .unwrap_or(0)
         - 2;
 
-    let to_column = (*state)
+    let to_column = state
         .game
         .hive()
         .iter()
@@ -336,9 +559,9 @@ fn FnApp() -> Html {
             <div class={if row % 2 != 0 {"sangria"} else {""}}>
             { for ((from_column - (row as f64 / 2.0).floor() as i8)..to_column).map(|column| {
                     html! {
-                    <button class={format!("tile hex {}", if (*state).possible_moves.contains(&(column, row).into()) {"possible-move"} else {""})} onclick={let state = state.clone(); Callback::from(move |_| state.set(update((*state).clone(), Msg::Coordinate((column, row)))))}>
+                    <button class={format!("tile hex {}", if state.possible_moves.contains(&(column, row).into()) {"possible-move"} else {""})} onclick={let state = state.clone(); Callback::from(move |_| state.set(update((*state).clone(), Msg::Coordinate((column, row)))))}>
                     {
-                        (*state).game.get_top_piece((column, row).into()).map(|p| format!("{p}\n({column},{row})")).unwrap_or(format!("({column},{row})"))
+                        state.game.get_top_piece((column, row).into()).map(|p| format!("{p}\n({column},{row})")).unwrap_or(format!("({column},{row})"))
                     }
                     </button>
                     }
@@ -349,9 +572,9 @@ fn FnApp() -> Html {
         </div>
     };
 
-    let pool = (*state).game.get_pool();
-    let whites = pool.iter().filter(|p| p.color == piece::Color::White);
-    let blacks = pool.iter().filter(|p| p.color == piece::Color::Black);
+    // Only the next piece of each bug can be placed
+    let whites = state.game.placeable_pieces(&piece::Color::White);
+    let blacks = state.game.placeable_pieces(&piece::Color::Black);
 
     html! {
         <div>
@@ -359,22 +582,78 @@ fn FnApp() -> Html {
             {board}
             <p>
             {
-                if let Some(pos) = &(state).selected {
-                    format!("Selected: {:?}", pos)
+                if let Some(selected) = &state.selected {
+                    format!("Selected: {}", selection_name(&state.game, selected))
                 } else {
                     "No selection".to_string()
                 }
             }
             </p>
+            <p>
+            {
+                if let Some(game::Action::Pass) = state.game.history().last() {
+                    format!("{} had no legal actions and passed", !state.game.turn().clone())
+                } else {
+                    "".to_string()
+                }
+            }
+            </p>
+            <p>{ format!("Moves: {}", notation::history(&state.game).join(", ")) }</p>
+            <p>{ evaluation(&state.game) }</p>
+            <button class="button" onclick={
+                let state = state.clone();
+                Callback::from(move |_| state.set(update((*state).clone(), Msg::Undo)))
+            }>{ "Undo" }</button>
+            <button class="button" onclick={
+                let state = state.clone();
+                Callback::from(move |_| state.set(update((*state).clone(), Msg::Redo)))
+            }>{ "Redo" }</button>
+            <button class="button" onclick={
+                let state = state.clone();
+                Callback::from(move |_| state.set(update((*state).clone(), Msg::Throw)))
+            }>{ "Throw with pillbug" }</button>
+            <button class="button" onclick={
+                let state = state.clone();
+                Callback::from(move |_| state.set(update((*state).clone(), Msg::ComputerMove)))
+            }>{ "Computer move" }</button>
+            <p>
+            {
+                if let Some(pillbug) = state.pillbug {
+                    format!("Throwing with {}", selection_name(&state.game, &Selection::Coordinate(pillbug)))
+                } else {
+                    "".to_string()
+                }
+            }
+            </p>
+            {
+                for piece::EXPANSIONS.iter().map(|bug| {
+                    let enabled = state.expansions.contains(bug);
+                    html! {
+                        <button class="button" onclick={
+                            let bug = bug.clone();
+                            let state = state.clone();
+                            Callback::from(move |_| state.set(update((*state).clone(), Msg::Expansion(bug.clone()))))
+                        }>
+                        { format!("{} {}", bug, if enabled { "on" } else { "off" }) }
+                        </button>
+                    }
+                })
+            }
+            <button class="button" onclick={
+                let state = state.clone();
+                Callback::from(move |_| state.set(update((*state).clone(), Msg::Tournament)))
+            }>
+            { format!("Tournament rules {}", if state.tournament { "on" } else { "off" }) }
+            </button>
 
         <div class="container">
             <div class="row">
 
             <div class="col">
             {
-                for blacks.map(|piece|{
+                for blacks.iter().map(|piece|{
                         html!{
-                            <button class="button" onclick={
+                            <button class="button" disabled={greyed_out(&state.game, piece)} onclick={
                                 let piece = piece.clone(); // TODO: what is the right way to do this?
                                 let state = state.clone();
 
@@ -392,9 +671,9 @@ fn FnApp() -> Html {
 
             <div class="col">
             {
-                for whites.map(|piece|{
+                for whites.iter().map(|piece|{
                         html!{
-                            <button class="button" onclick={
+                            <button class="button" disabled={greyed_out(&state.game, piece)} onclick={
                                 let piece = piece.clone(); // TODO: what is the right way to do this?
                                 let state = state.clone();
                                 Callback::from(move |_| state.set(update((*state).clone(), Msg::Piece(piece.clone()))))
@@ -412,7 +691,7 @@ fn FnApp() -> Html {
 
             <p>
             {
-                (*state).game_error.to_string()
+                state.game_error.to_string()
             }
             </p>
         </div>
@@ -0,0 +1,260 @@
+// Monte Carlo Tree Search: grows a tree of actions, choosing which to look at with UCT
+// and scoring new positions by playing them out until the end of the game or a number of actions
+// Like the other engines, it makes and unmakes actions on the game it's given
+
+use std::time::{Duration, Instant};
+
+use crate::engine::Player;
+use crate::game::{Action, Game, GameResult};
+use crate::piece::Color;
+use crate::rng::Rng;
+
+// How the actions of a playout are chosen
+pub trait PlayoutPolicy {
+    // One of the legal actions of the game, which are never empty
+    fn pick(&self, game: &mut Game, actions: &[Action], rng: &mut Rng) -> usize;
+}
+
+// Every legal action is as likely
+#[derive(Default)]
+pub struct RandomPlayout;
+
+impl PlayoutPolicy for RandomPlayout {
+    fn pick(&self, _game: &mut Game, actions: &[Action], rng: &mut Rng) -> usize {
+        rng.below(actions.len())
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Budget {
+    // Playouts to make
+    Iterations(u32),
+    // The search goes on until the time is up, always making at least one playout
+    Time(Duration),
+}
+
+struct Node {
+    // The action leading to the node and who made it, none for the root
+    action: Option<Action>,
+    mover: Option<Color>,
+    parent: Option<usize>,
+    children: Vec<usize>,
+    // Legal actions without a child yet
+    untried: Vec<Action>,
+    visits: u32,
+    // Won playouts count 1 and drawn or unfinished ones 1/2, for the player that made the action
+    reward: f64,
+}
+
+impl Node {
+    fn new(
+        game: &mut Game,
+        parent: Option<usize>,
+        action: Option<Action>,
+        mover: Option<Color>,
+    ) -> Self {
+        let untried = match game.result() {
+            Some(_) => vec![],
+            None => game.legal_actions(),
+        };
+
+        Node {
+            action,
+            mover,
+            parent,
+            children: vec![],
+            untried,
+            visits: 0,
+            reward: 0.0,
+        }
+    }
+}
+
+pub struct Mcts<P> {
+    pub policy: P,
+    pub budget: Budget,
+    // How much UCT favours the actions looked at the least
+    pub exploration: f64,
+    // Actions a playout makes before it's taken as a draw
+    pub playout_limit: usize,
+    rng: Rng,
+}
+
+impl<P: PlayoutPolicy> Mcts<P> {
+    pub fn new(policy: P, budget: Budget, seed: u64) -> Self {
+        Mcts {
+            policy,
+            budget,
+            exploration: std::f64::consts::SQRT_2,
+            playout_limit: 50,
+            rng: Rng::new(seed),
+        }
+    }
+
+    // Goes down the tree, grows it by one action and plays it out, leaving the game as it was
+    fn iterate(&mut self, game: &mut Game, tree: &mut Vec<Node>) {
+        let mut node = 0;
+        let mut made = 0;
+
+        // Selection, down to a node with actions left to try
+        while tree[node].untried.is_empty() && !tree[node].children.is_empty() {
+            node = self.select(tree, node);
+            let action = tree[node].action.clone();
+            game.make(action.expect("only the root has no action"))
+                .expect("the actions in the tree were legal when they were added");
+            made += 1;
+        }
+
+        // Expansion
+        if !tree[node].untried.is_empty() {
+            let index = self.rng.below(tree[node].untried.len());
+            let action = tree[node].untried.swap_remove(index);
+            let mover = game.turn().clone();
+            game.make(action.clone())
+                .expect("legal actions can always be made");
+
+            let child = tree.len();
+            tree.push(Node::new(game, Some(node), Some(action), Some(mover)));
+            tree[node].children.push(child);
+            node = child;
+            made += 1;
+        }
+
+        let winner = self.playout(game);
+
+        // Backpropagation
+        let mut current = Some(node);
+        while let Some(index) = current {
+            let node = &mut tree[index];
+            node.visits += 1;
+            node.reward += match (&winner, &node.mover) {
+                (Some(winner), Some(mover)) if winner == mover => 1.0,
+                (Some(_), Some(_)) => 0.0,
+                _ => 0.5,
+            };
+            current = node.parent;
+        }
+
+        for _ in 0..made {
+            game.unmake().expect("the actions were just made");
+        }
+    }
+
+    // The child with the highest upper confidence bound
+    fn select(&self, tree: &[Node], node: usize) -> usize {
+        let parent_visits = (tree[node].visits as f64).ln();
+        let uct = |child: &Node| {
+            let visits = child.visits as f64;
+            child.reward / visits + self.exploration * (parent_visits / visits).sqrt()
+        };
+
+        *tree[node]
+            .children
+            .iter()
+            .max_by(|&&a, &&b| uct(&tree[a]).total_cmp(&uct(&tree[b])))
+            .expect("only nodes with children are selected from")
+    }
+
+    // Plays the game out and takes it back, telling who won if anyone did
+    fn playout(&mut self, game: &mut Game) -> Option<Color> {
+        let mut made = 0;
+        while made < self.playout_limit && game.result().is_none() {
+            let actions = game.legal_actions();
+            if actions.is_empty() {
+                break;
+            }
+
+            let index = self.policy.pick(game, &actions, &mut self.rng);
+            game.make(actions[index].clone())
+                .expect("legal actions can always be made");
+            made += 1;
+        }
+
+        let winner = match game.result() {
+            Some(GameResult::Win(color)) => Some(color.clone()),
+            _ => None,
+        };
+
+        for _ in 0..made {
+            game.unmake().expect("the actions were just made");
+        }
+        winner
+    }
+}
+
+impl<P: PlayoutPolicy> Player for Mcts<P> {
+    fn choose(&mut self, game: &mut Game) -> Option<Action> {
+        let mut tree = vec![Node::new(game, None, None, None)];
+        if tree[0].untried.is_empty() {
+            return None;
+        }
+
+        match self.budget {
+            Budget::Iterations(iterations) => {
+                for _ in 0..iterations.max(1) {
+                    self.iterate(game, &mut tree);
+                }
+            }
+            Budget::Time(time) => {
+                let deadline = Instant::now() + time;
+                self.iterate(game, &mut tree);
+                while Instant::now() < deadline {
+                    self.iterate(game, &mut tree);
+                }
+            }
+        }
+
+        // The most visited action is the one the search trusts the most
+        tree[0]
+            .children
+            .iter()
+            .max_by_key(|&&child| tree[child].visits)
+            .and_then(|&child| tree[child].action.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coordinate::XYCoordinate;
+    use crate::piece::{Bug, Piece};
+    use crate::test_positions::WIN_IN_ONE;
+
+    #[test]
+    fn mcts_finds_the_win() {
+        let mut game = Game::from_game_string(WIN_IN_ONE).unwrap();
+
+        let mut player = Mcts::new(RandomPlayout, Budget::Iterations(300), 1);
+        player.playout_limit = 4;
+
+        assert_eq!(
+            player.choose(&mut game),
+            Some(Action::Move {
+                piece: Piece {
+                    bug: Bug::Ant,
+                    color: Color::White,
+                    number: 1,
+                },
+                from: XYCoordinate { x: -1, y: 4 },
+                to: XYCoordinate { x: 2, y: -1 },
+            })
+        );
+        assert_eq!(game.to_game_string(), Ok(WIN_IN_ONE.to_string()));
+    }
+
+    #[test]
+    fn same_seed_same_choice() {
+        let mut game = Game::from_game_string(WIN_IN_ONE).unwrap();
+        game.undo().unwrap();
+
+        let choose = |seed| {
+            let mut player = Mcts::new(RandomPlayout, Budget::Iterations(30), seed);
+            player.playout_limit = 4;
+            player.choose(&mut game.clone())
+        };
+
+        assert_eq!(choose(7), choose(7));
+        assert_eq!(Rng::new(7).next_u64(), Rng::new(7).next_u64());
+        assert_ne!(Rng::new(7).next_u64(), Rng::new(8).next_u64());
+    }
+}
@@ -0,0 +1,410 @@
+// Standard Hive notation: a piece's name and where it goes relative to another piece, e.g. "wA1 -bQ"
+// Pieces are named after their color, their bug and their number if there are more like them
+// "-X", "\X", "X/", "X-", "X\" and "/X" are the cells around X, and a bare "X" is on top of it
+
+use std::collections::HashMap;
+
+use crate::coordinate::{XYCoordinate, RELATIVE_NEIGHBORS_CLOCKWISE};
+use crate::game::{Action, Game, GameError};
+use crate::piece::{Bug, Color, Piece, BUILT_IN};
+
+// How a cell is written relative to a neighbor, following RELATIVE_NEIGHBORS_CLOCKWISE
+// The boolean tells if the symbol goes before the neighbor's name
+const DIRECTIONS: [(char, bool); 6] = [
+    ('-', true),
+    ('\\', true),
+    ('/', false),
+    ('-', false),
+    ('\\', false),
+    ('/', true),
+];
+
+#[derive(Debug, PartialEq)]
+pub enum NotationError {
+    UnknownPiece(String),
+    UnknownPosition(String),
+    MissingPosition(String),
+    PieceCovered(String),
+    Illegal(GameError),
+}
+
+impl std::fmt::Display for NotationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            NotationError::UnknownPiece(name) => write!(f, "Unknown piece {name}"),
+            NotationError::UnknownPosition(position) => write!(f, "Unknown position {position}"),
+            NotationError::MissingPosition(name) => write!(f, "{name} needs a position"),
+            NotationError::PieceCovered(name) => write!(f, "{name} is covered by another piece"),
+            NotationError::Illegal(error) => write!(f, "{error}"),
+        }
+    }
+}
+
+// Pieces on each cell, from the bottom to the top of the stack
+type Stacks = HashMap<XYCoordinate, Vec<Piece>>;
+
+// Turns the notation into the action it stands for, as long as it's legal in the game
+pub fn parse(game: &mut Game, text: &str) -> Result<Action, NotationError> {
+    let text = text.trim();
+
+    // Passing is never among the legal actions, the game tells if it's the only thing left to do
+    if text == "pass" {
+        return match game.clone().play(Action::Pass) {
+            Ok(()) => Ok(Action::Pass),
+            Err(error) => Err(NotationError::Illegal(error)),
+        };
+    }
+
+    let (piece, to) = match text.split_once(' ') {
+        Some((name, position)) => parse_target(game, name, Some(position.trim()))?,
+        None => parse_target(game, text, None)?,
+    };
+
+    action(game, piece, to)
+}
+
+// The legal action that takes the piece to the cell: placing it if it's still in the pool, or else moving it
+// Throws are written as plain moves, so the legal actions tell which one it is
+pub fn action(game: &mut Game, piece: Piece, to: XYCoordinate) -> Result<Action, NotationError> {
+    let stacks = stacks(game.history());
+
+    let action = match find(&stacks, &piece) {
+        None => Action::Put { piece, to },
+        Some(from) if stacks[&from].last() == Some(&piece) => Action::Move { piece, from, to },
+        Some(_) => return Err(NotationError::PieceCovered(name(&piece))),
+    };
+
+    let legal = game.legal_actions().into_iter().find(|legal| {
+        legal == &action
+            || matches!(
+                (legal, &action),
+                (Action::Throw { from, to, .. }, Action::Move { from: f, to: t, .. })
+                    if from == f && to == t
+            )
+    });
+
+    legal.ok_or_else(|| illegal(game, action))
+}
+
+// Writes a legal action of the game in notation
+pub fn write(game: &mut Game, action: &Action) -> Result<String, NotationError> {
+    if !game.legal_actions().contains(action) {
+        return Err(illegal(game, action.clone()));
+    }
+
+    Ok(write_action(&stacks(game.history()), action))
+}
+
+// Every legal action of the game in notation
+pub fn legal_moves(game: &mut Game) -> Vec<String> {
+    let stacks = stacks(game.history());
+
+    game.legal_actions()
+        .iter()
+        .map(|action| write_action(&stacks, action))
+        .collect()
+}
+
+// Every action played so far in notation, passes included
+pub fn history(game: &Game) -> Vec<String> {
+    let mut stacks = Stacks::new();
+
+    game.history()
+        .iter()
+        .map(|action| {
+            let text = write_action(&stacks, action);
+            apply(&mut stacks, action);
+            text
+        })
+        .collect()
+}
+
+// e.g. "wA1" for the first white ant, or "bQ" for the black bee
+pub fn name(piece: &Piece) -> String {
+    let color = match piece.color {
+        Color::White => 'w',
+        Color::Black => 'b',
+    };
+
+    match piece.bug.is_numbered() {
+        true => format!("{color}{}{}", bug_letter(&piece.bug), piece.number),
+        false => format!("{color}{}", bug_letter(&piece.bug)),
+    }
+}
+
+pub fn bug_letter(bug: &Bug) -> char {
+    match bug {
+        Bug::Bee => 'Q',
+        Bug::Beetle => 'B',
+        Bug::Grasshopper => 'G',
+        Bug::Spider => 'S',
+        Bug::Ant => 'A',
+        Bug::Mosquito => 'M',
+        Bug::Ladybug => 'L',
+        Bug::Pillbug => 'P',
+        Bug::Custom(letter) => *letter,
+    }
+}
+
+// The game tells why the action can't be played, trying it on a copy
+fn illegal(game: &Game, action: Action) -> NotationError {
+    let error = game.clone().play(action).err();
+    NotationError::Illegal(error.unwrap_or(GameError::InvalidMove))
+}
+
+// The piece a name like "wA1" stands for
+pub fn parse_piece(name: &str) -> Result<Piece, NotationError> {
+    let unknown = || NotationError::UnknownPiece(name.to_string());
+
+    let mut chars = name.chars();
+    let color = match chars.next() {
+        Some('w') => Color::White,
+        Some('b') => Color::Black,
+        _ => return Err(unknown()),
+    };
+    let letter = chars.next().ok_or_else(unknown)?;
+    let bug = BUILT_IN
+        .into_iter()
+        .find(|bug| bug_letter(bug) == letter)
+        // Any other capital letter is a homebrew bug, which the game knows of if it was registered
+        .or_else(|| letter.is_ascii_uppercase().then_some(Bug::Custom(letter)))
+        .ok_or_else(unknown)?;
+
+    let number = match chars.as_str() {
+        "" if !bug.is_numbered() => 1,
+        number if bug.is_numbered() => number.parse().map_err(|_| unknown())?,
+        _ => return Err(unknown()),
+    };
+
+    Ok(Piece { bug, color, number })
+}
+
+// The piece a text like "wA1 -bQ" names and the cell it goes to
+fn parse_target(
+    game: &Game,
+    name: &str,
+    position: Option<&str>,
+) -> Result<(Piece, XYCoordinate), NotationError> {
+    let piece = parse_piece(name)?;
+
+    let to = match position {
+        // The first piece of the game goes anywhere
+        None if game.history().is_empty() => XYCoordinate { x: 0, y: 0 },
+        None => return Err(NotationError::MissingPosition(name.to_string())),
+        Some(position) => locate(game, position)?,
+    };
+
+    Ok((piece, to))
+}
+
+// The cell a position like "-wQ" stands for in the game
+pub fn locate(game: &Game, position: &str) -> Result<XYCoordinate, NotationError> {
+    parse_position(&stacks(game.history()), position)
+}
+
+fn parse_position(stacks: &Stacks, position: &str) -> Result<XYCoordinate, NotationError> {
+    let unknown = || NotationError::UnknownPosition(position.to_string());
+    let locate = |name| {
+        let piece = parse_piece(name).map_err(|_| unknown())?;
+        find(stacks, &piece).ok_or_else(unknown)
+    };
+
+    for (&(symbol, before), &direction) in DIRECTIONS.iter().zip(&RELATIVE_NEIGHBORS_CLOCKWISE) {
+        let neighbor = if before {
+            position.strip_prefix(symbol)
+        } else {
+            position.strip_suffix(symbol)
+        };
+
+        if let Some(neighbor) = neighbor {
+            return Ok(locate(neighbor)? + direction);
+        }
+    }
+
+    // On top of the named piece
+    locate(position)
+}
+
+fn find(stacks: &Stacks, piece: &Piece) -> Option<XYCoordinate> {
+    stacks
+        .iter()
+        .find(|(_, stack)| stack.contains(piece))
+        .map(|(&c, _)| c)
+}
+
+// Writes the action as seen from the stacks before it was played
+fn write_action(stacks: &Stacks, action: &Action) -> String {
+    let mut stacks = stacks.clone();
+
+    let (piece, to) = match action {
+        Action::Pass => return "pass".to_string(),
+        Action::Put { piece, to } => (piece, *to),
+        Action::Move { piece, from, to }
+        | Action::Throw {
+            piece, from, to, ..
+        } => {
+            take(&mut stacks, *from);
+            (piece, *to)
+        }
+    };
+    let moved = name(piece);
+
+    if let Some(top) = stacks.get(&to).and_then(|stack| stack.last()) {
+        return format!("{moved} {}", name(top));
+    }
+
+    // Relative to the first neighbor clockwise, so every action has a single way of being written
+    for (&(symbol, before), &direction) in DIRECTIONS.iter().zip(&RELATIVE_NEIGHBORS_CLOCKWISE) {
+        if let Some(neighbor) = stacks.get(&(to - direction)).and_then(|stack| stack.last()) {
+            let neighbor = name(neighbor);
+            return match before {
+                true => format!("{moved} {symbol}{neighbor}"),
+                false => format!("{moved} {neighbor}{symbol}"),
+            };
+        }
+    }
+
+    // The first piece of the game
+    moved
+}
+
+fn take(stacks: &mut Stacks, coordinate: XYCoordinate) -> Option<Piece> {
+    let stack = stacks.get_mut(&coordinate)?;
+    let piece = stack.pop();
+    if stack.is_empty() {
+        stacks.remove(&coordinate);
+    }
+    piece
+}
+
+fn apply(stacks: &mut Stacks, action: &Action) {
+    match action {
+        Action::Pass => {}
+        Action::Put { piece, to } => stacks.entry(*to).or_default().push(piece.clone()),
+        Action::Move { from, to, .. } | Action::Throw { from, to, .. } => {
+            if let Some(piece) = take(stacks, *from) {
+                stacks.entry(*to).or_default().push(piece);
+            }
+        }
+    }
+}
+
+// Where every piece is, replaying the history
+fn stacks(history: &[Action]) -> Stacks {
+    let mut stacks = Stacks::new();
+    for action in history {
+        apply(&mut stacks, action);
+    }
+    stacks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rules::RuleSet;
+
+    #[test]
+    fn reads_and_writes_actions() {
+        let mut game = Game::new(RuleSet {
+            first_player: Color::White,
+            ..Default::default()
+        });
+
+        for text in [
+            "wQ",
+            "bQ wQ-",
+            "wB1 -wQ",
+            "bB1 bQ/",
+            "wA1 \\wB1",
+            "bB1 bQ",
+            "wA1 -wB1",
+        ] {
+            let action = parse(&mut game, text).unwrap();
+            assert_eq!(write(&mut game, &action), Ok(text.to_string()));
+            game.play(action).unwrap();
+        }
+
+        // The beetle goes down next to the white queen, which is written from the first neighbor clockwise
+        let action = parse(&mut game, "bB1 wQ/").unwrap();
+        assert_eq!(write(&mut game, &action), Ok("bB1 \\bQ".to_string()));
+        game.play(action).unwrap();
+
+        assert_eq!(
+            game.get_top_piece((0, 1).into()).map(name),
+            Some("bB1".to_string())
+        );
+        assert_eq!(history(&game).last(), Some(&"bB1 \\bQ".to_string()));
+        assert_eq!(history(&game).len(), 8);
+    }
+
+    #[test]
+    fn every_legal_action_reads_back() {
+        let mut game = Game::new(RuleSet {
+            expansions: vec![Bug::Pillbug],
+            first_player: Color::White,
+            ..Default::default()
+        });
+
+        for text in ["wP", "bQ wP-", "wQ -wP", "bA1 bQ/", "wA1 /wQ", "bA2 bQ\\"] {
+            let action = parse(&mut game, text).unwrap();
+            game.play(action).unwrap();
+        }
+
+        let moves = legal_moves(&mut game);
+        assert_eq!(moves.len(), game.legal_actions().len());
+
+        // Throws and moves between the same cells are written the same way, so they read back as either
+        for text in moves {
+            let action = parse(&mut game, &text).unwrap();
+            assert_eq!(write(&mut game, &action), Ok(text));
+        }
+    }
+
+    #[test]
+    fn refuses_what_the_game_would() {
+        let mut game = Game::new(RuleSet {
+            first_player: Color::White,
+            ..Default::default()
+        });
+
+        let action = parse(&mut game, "wS1").unwrap();
+        game.play(action).unwrap();
+
+        assert_eq!(
+            parse(&mut game, "bA2 wS1-"),
+            Err(NotationError::Illegal(GameError::PlacedOutOfOrder))
+        );
+        assert_eq!(
+            parse(&mut game, "bA1 wS1"),
+            Err(NotationError::Illegal(
+                GameError::SpawnedOnTopOfAnotherPiece
+            ))
+        );
+        assert_eq!(
+            parse(&mut game, "bA1 bQ-"),
+            Err(NotationError::UnknownPosition("bQ-".to_string()))
+        );
+        assert_eq!(
+            parse(&mut game, "bX1 wS1-"),
+            Err(NotationError::UnknownPiece("bX1".to_string()))
+        );
+        assert_eq!(
+            parse(&mut game, "pass"),
+            Err(NotationError::Illegal(GameError::InvalidMove))
+        );
+        assert_eq!(
+            write(
+                &mut game,
+                &Action::Put {
+                    piece: Piece {
+                        bug: Bug::Ant,
+                        color: Color::Black,
+                        number: 1,
+                    },
+                    to: (5, 5).into()
+                }
+            ),
+            Err(NotationError::Illegal(GameError::SpawnedOutOfHive))
+        );
+    }
+}
@@ -1,44 +1,105 @@
-#[derive(PartialEq, Debug, Clone)]
-pub(crate) struct Piece {
-    pub(crate) bug: Bug,
-    pub(crate) color: Color,
+use std::collections::HashSet;
+
+use crate::board::Zobrist;
+use crate::bugs::BugRegistry;
+use crate::coordinate::XYCoordinate;
+use crate::game::{Board, GameError};
+use crate::rng::splitmix;
+
+#[derive(PartialEq, Eq, Hash, Debug, Clone, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Piece {
+    pub bug: Bug,
+    pub color: Color,
+    // Tells apart the pieces of the same bug and color, they are placed from the lowest number up
+    pub number: u8,
 }
 
-#[derive(PartialEq, Debug, Clone)]
-pub(crate) enum Bug {
+#[derive(PartialEq, Eq, Hash, Debug, Clone, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Bug {
     Bee,
     Beetle,
     Grasshopper,
     Spider,
     Ant,
+    Mosquito,
+    Ladybug,
+    Pillbug,
+    // A homebrew bug, written with its letter in the notation, that moves as its BugRegistry entry says
+    // The registry refuses letters that aren't capital or that are taken by the bugs that come with the game
+    Custom(char),
 }
 
-pub(crate) trait BugTrait: PartialEq + std::fmt::Debug + Clone {}
+// Bugs that come with the game, homebrew ones aside
+pub const BUILT_IN: [Bug; 8] = [
+    Bug::Bee,
+    Bug::Beetle,
+    Bug::Grasshopper,
+    Bug::Spider,
+    Bug::Ant,
+    Bug::Mosquito,
+    Bug::Ladybug,
+    Bug::Pillbug,
+];
+
+// Bugs that can be added to the base game, in the order the game strings write them
+pub const EXPANSIONS: [Bug; 3] = [Bug::Mosquito, Bug::Ladybug, Bug::Pillbug];
+
+impl Bug {
+    // Bugs that come more than once in the box, which go by their number in the notation
+    pub fn is_numbered(&self) -> bool {
+        matches!(
+            self,
+            Bug::Beetle | Bug::Grasshopper | Bug::Spider | Bug::Ant
+        )
+    }
+}
 
-impl BugTrait for Bug {}
+// How a bug moves: the cells the top piece at 'from' can go to
+// Bugs that borrow other bugs' moves can look them up in the registry
+pub trait BugTrait {
+    fn possible_moves(
+        &self,
+        board: &mut Board,
+        from: XYCoordinate,
+        bugs: &BugRegistry,
+    ) -> Result<HashSet<XYCoordinate>, GameError>;
+}
 
 impl std::fmt::Display for Bug {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(
-            f,
-            "{}",
-            match self {
-                Bug::Bee => "🐝",
-                Bug::Beetle => "🪲",
-                Bug::Grasshopper => "🦗",
-                Bug::Spider => "🕷",
-                Bug::Ant => "🐜",
-            }
-        )
+        let emoji = match self {
+            Bug::Bee => "🐝",
+            Bug::Beetle => "🪲",
+            Bug::Grasshopper => "🦗",
+            Bug::Spider => "🕷",
+            Bug::Ant => "🐜",
+            Bug::Mosquito => "🦟",
+            Bug::Ladybug => "🐞",
+            Bug::Pillbug => "🐛",
+            Bug::Custom(letter) => return write!(f, "{letter}"),
+        };
+        write!(f, "{emoji}")
     }
 }
 
-#[derive(PartialEq, Debug, Clone)]
-pub(crate) enum Color {
+#[derive(PartialEq, Eq, Hash, Debug, Clone, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Color {
     Black,
     White,
 }
 
+impl Zobrist for Color {
+    fn zobrist_id(&self) -> u64 {
+        match self {
+            Color::Black => 0,
+            Color::White => 1,
+        }
+    }
+}
+
 impl std::ops::Not for Color {
     type Output = Color;
 
@@ -63,8 +124,30 @@ impl std::fmt::Display for Color {
     }
 }
 
+impl Zobrist for Piece {
+    fn zobrist_id(&self) -> u64 {
+        let bug = match self.bug {
+            Bug::Bee => 0,
+            Bug::Beetle => 1,
+            Bug::Grasshopper => 2,
+            Bug::Spider => 3,
+            Bug::Ant => 4,
+            Bug::Mosquito => 5,
+            Bug::Ladybug => 6,
+            Bug::Pillbug => 7,
+            // Past the built-in bugs
+            Bug::Custom(letter) => 8 + letter as u64,
+        };
+        splitmix(&[bug, self.color.zobrist_id(), self.number.into()])
+    }
+}
+
 impl std::fmt::Display for Piece {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{} {}", self.color, self.bug)
+        if self.bug.is_numbered() {
+            write!(f, "{} {}{}", self.color, self.bug, self.number)
+        } else {
+            write!(f, "{} {}", self.color, self.bug)
+        }
     }
 }
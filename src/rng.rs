@@ -0,0 +1,46 @@
+// SplitMix64, for the random playouts and the Zobrist keys
+// Its numbers only depend on the seed, the same with every Rust version and platform
+
+#[derive(Debug, Clone)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Rng { state: seed }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
+
+    // A number from 0 up to, but not including, the given one
+    pub fn below(&mut self, n: usize) -> usize {
+        (self.next_u64() % n as u64) as usize
+    }
+}
+
+// Mixes the numbers one after the other into a single one, e.g. splitmix(&[bug, color, number])
+pub fn splitmix(values: &[u64]) -> u64 {
+    values
+        .iter()
+        .fold(0, |state, &value| Rng::new(state ^ value).next_u64())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn follows_splitmix64() {
+        let mut rng = Rng::new(0);
+        assert_eq!(rng.next_u64(), 0xe220a8397b1dcdaf); // the reference implementation's first numbers
+        assert_eq!(rng.next_u64(), 0x6e789e6aa1b965f4);
+        assert_eq!(splitmix(&[0]), 0xe220a8397b1dcdaf);
+    }
+}
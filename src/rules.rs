@@ -0,0 +1,79 @@
+use crate::piece::{Bug, Color, Piece};
+
+// The rules a game is played with, e.g. RuleSet { first_player: Color::White, ..Default::default() }
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RuleSet {
+    // Bugs added to the base game, one of each per color
+    pub expansions: Vec<Bug>,
+    // The turn by which each player must have placed their bee, counting their own turns from 1
+    pub queen_deadline: u8,
+    // The bee can't be placed on each player's first turn
+    pub tournament_opening: bool,
+    pub first_player: Color,
+    // The game is a draw once both players made this many turns without a result
+    pub move_limit: Option<u16>,
+    // The game is a draw once the same position is reached for the third time
+    pub threefold_repetition: bool,
+}
+
+impl Default for RuleSet {
+    // TODO: black starts by default, but the official rules have white starting
+    fn default() -> Self {
+        RuleSet {
+            expansions: vec![],
+            queen_deadline: 4,
+            tournament_opening: false,
+            first_player: Color::Black,
+            move_limit: None,
+            threefold_repetition: true,
+        }
+    }
+}
+
+impl RuleSet {
+    // The rules of official tournaments: white moves first and nobody places their bee on their first turn
+    pub fn tournament() -> Self {
+        RuleSet {
+            tournament_opening: true,
+            first_player: Color::White,
+            ..Default::default()
+        }
+    }
+
+    // The rules other UHP engines play with, the tournament ones
+    // They don't end games on repetitions, so neither do the games they send us
+    pub fn uhp() -> Self {
+        RuleSet {
+            threefold_repetition: false,
+            ..RuleSet::tournament()
+        }
+    }
+
+    // The base game pool plus one of each expansion bug per color
+    pub fn pool(&self) -> Vec<Piece> {
+        [Color::Black, Color::White]
+            .iter()
+            .flat_map(|color| {
+                let expansion_bugs = self.expansions.iter().map(|bug| (1, bug.clone()));
+
+                [
+                    (1, Bug::Bee),
+                    (2, Bug::Beetle),
+                    (2, Bug::Spider),
+                    (3, Bug::Ant),
+                    (3, Bug::Grasshopper),
+                ]
+                .into_iter()
+                .chain(expansion_bugs)
+                .flat_map(move |(count, bug)| {
+                    (1..=count).map(move |number| Piece {
+                        bug: bug.clone(),
+                        color: color.clone(),
+                        number,
+                    })
+                })
+            })
+            .collect()
+    }
+}
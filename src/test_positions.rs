@@ -0,0 +1,4 @@
+// Positions shared by the tests of the engines and the game
+
+// White surrounds the black bee by moving its first ant from (-1, 4) to (2, -1), and nothing else wins
+pub const WIN_IN_ONE: &str = "Base;InProgress;White[12];wB1;bB1 /wB1;wB2 wB1/;bQ bB1\\;wS1 -wB2;bB2 -bQ;wQ \\wB2;bA1 -bB1;wQ wB2/;bQ bB1-;wS2 wB2-;bA1 bQ/;wG1 wS2-;bA2 -bB2;wG2 \\wQ;bA2 wG1-;wA1 wG2/;bA3 /bB2;wA2 wA1/;bA3 bB2-;wA2 bA3-;bG1 /bB2";
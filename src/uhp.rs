@@ -0,0 +1,483 @@
+// Universal Hive Protocol, to play against other engines and from other interfaces
+// Spec at https://github.com/jonthysell/Mzinga/wiki/UniversalHiveProtocol
+
+use std::time::Duration;
+
+use crate::engine::{AlphaBeta, Limit, Player};
+use crate::evaluation::Heuristic;
+use crate::game::{Action, Game};
+use crate::notation;
+use crate::piece::Color;
+use crate::rules::RuleSet;
+
+#[derive(Debug, PartialEq)]
+pub enum UhpError {
+    // The command can't be run, answered with "err"
+    Command(String),
+    // The move is not valid in the current game, answered with "invalidmove"
+    Move(String),
+}
+
+// Rules that UHP games aren't played with, which can be changed through the options command
+const OPTIONS: [&str; 5] = [
+    "FirstPlayer",
+    "TournamentOpening",
+    "QueenDeadline",
+    "MoveLimit",
+    "ThreefoldRepetition",
+];
+
+pub struct Engine {
+    game: Option<Game>,
+    // The games started from then on are played with these, along with the expansions they are given
+    rules: RuleSet,
+}
+
+impl Default for Engine {
+    fn default() -> Self {
+        Engine {
+            game: None,
+            rules: RuleSet::uhp(),
+        }
+    }
+}
+
+impl Engine {
+    // Runs a single command, the answer always ends with "ok"
+    pub fn handle(&mut self, command: &str) -> String {
+        let mut words = command.split_whitespace();
+        let name = words.next().unwrap_or_default();
+        let arguments: Vec<&str> = words.collect();
+
+        let output = match name {
+            "info" => Ok(self.info()),
+            "newgame" => self.new_game(&arguments.join(" ")),
+            "play" => self.play(&arguments.join(" ")),
+            "pass" => self.play("pass"),
+            "validmoves" => self.valid_moves(),
+            "bestmove" => self.best_move(&arguments),
+            "undo" => self.undo(arguments.first().copied()),
+            "options" => self.options(&arguments),
+            _ => Err(UhpError::Command(format!("Unknown command '{name}'"))),
+        };
+
+        match output {
+            Ok(output) if output.is_empty() => "ok".to_string(),
+            Ok(output) => format!("{output}\nok"),
+            Err(UhpError::Command(message)) => format!("err {message}\nok"),
+            Err(UhpError::Move(message)) => format!("invalidmove {message}\nok"),
+        }
+    }
+
+    fn info(&self) -> String {
+        format!(
+            "id hive-rust v{}\nMosquito;Ladybug;Pillbug",
+            env!("CARGO_PKG_VERSION")
+        )
+    }
+
+    // Takes either a GameTypeString or a whole GameString
+    fn new_game(&mut self, game: &str) -> Result<String, UhpError> {
+        let rules = self.rules.clone();
+        let game = match game {
+            "" => Game::from_game_type_with_rules("Base", rules),
+            game if game.contains(';') => Game::from_game_string_with_rules(game, rules),
+            game_type => Game::from_game_type_with_rules(game_type, rules),
+        };
+        self.game = Some(game.map_err(|error| UhpError::Command(error.to_string()))?);
+
+        self.game_string()
+    }
+
+    // Also plays "pass", which is the same as "play pass"
+    fn play(&mut self, text: &str) -> Result<String, UhpError> {
+        let game = self.game_mut()?;
+
+        let action =
+            notation::parse(game, text).map_err(|error| UhpError::Move(error.to_string()))?;
+        let played = game.history().len() + 1;
+        game.play(action)
+            .map_err(|error| UhpError::Move(error.to_string()))?;
+
+        // In UHP the player passes by themselves, so the pass the game made on its own is taken back
+        if game.history().len() > played {
+            game.take_back_pass()
+                .map_err(|error| UhpError::Command(error.to_string()))?;
+        }
+
+        self.game_string()
+    }
+
+    fn valid_moves(&mut self) -> Result<String, UhpError> {
+        let moves = notation::legal_moves(self.game_mut()?);
+
+        if moves.is_empty() {
+            return Ok("pass".to_string());
+        }
+        Ok(moves.join(";"))
+    }
+
+    // Searches for "time hh:mm:ss" or "depth n", a couple of moves ahead if not told
+    fn best_move(&mut self, arguments: &[&str]) -> Result<String, UhpError> {
+        let invalid = || UhpError::Command("Invalid bestmove limit".to_string());
+
+        let limit = match arguments {
+            [] => Limit::Depth(2),
+            ["depth", depth] => Limit::Depth(depth.parse().map_err(|_| invalid())?),
+            ["time", time] => {
+                let mut seconds = 0;
+                for part in time.split(':') {
+                    seconds = seconds * 60 + part.parse::<u64>().map_err(|_| invalid())?;
+                }
+                Limit::Time(Duration::from_secs(seconds))
+            }
+            _ => return Err(invalid()),
+        };
+
+        let game = self.game_mut()?;
+        let Some(action) = AlphaBeta::new(Heuristic::default(), limit).choose(game) else {
+            return Ok("pass".to_string());
+        };
+
+        notation::write(game, &action).map_err(|error| UhpError::Command(error.to_string()))
+    }
+
+    // Passes count as moves on their own
+    fn undo(&mut self, amount: Option<&str>) -> Result<String, UhpError> {
+        let amount: usize = amount
+            .unwrap_or("1")
+            .parse()
+            .map_err(|_| UhpError::Command("Invalid amount of moves to undo".to_string()))?;
+
+        let game = self.game_mut()?;
+        let target = game
+            .history()
+            .len()
+            .checked_sub(amount)
+            .ok_or(UhpError::Command("Not that many moves to undo".to_string()))?;
+
+        while game.history().len() > target {
+            let undone = match game.history().last() {
+                Some(Action::Pass) => game.take_back_pass(),
+                _ => game.undo(),
+            };
+            undone.map_err(|error| UhpError::Command(error.to_string()))?;
+        }
+
+        self.game_string()
+    }
+
+    // "options" lists every option, "options get Name" one of them and "options set Name Value" changes it
+    // Changes apply from the next newgame on
+    fn options(&mut self, arguments: &[&str]) -> Result<String, UhpError> {
+        match arguments {
+            [] => Ok(OPTIONS
+                .iter()
+                .map(|name| self.option(name))
+                .collect::<Result<Vec<_>, _>>()?
+                .join("\n")),
+            ["get", name] => self.option(name),
+            ["set", name, value] => {
+                self.set_option(name, value)?;
+                self.option(name)
+            }
+            [subcommand, ..] if !["get", "set"].contains(subcommand) => Err(UhpError::Command(
+                format!("Unknown options command '{subcommand}'"),
+            )),
+            _ => Err(UhpError::Command("Invalid options command".to_string())),
+        }
+    }
+
+    // "Name;Type;Value;Default", followed by the range of numbers or the choices
+    fn option(&self, name: &str) -> Result<String, UhpError> {
+        let (rules, uhp) = (&self.rules, RuleSet::uhp());
+
+        let option = match name {
+            "FirstPlayer" => format!(
+                "enum;{};{};White;Black",
+                color_name(&rules.first_player),
+                color_name(&uhp.first_player)
+            ),
+            "TournamentOpening" => format!(
+                "bool;{};{}",
+                bool_name(rules.tournament_opening),
+                bool_name(uhp.tournament_opening)
+            ),
+            "QueenDeadline" => format!(
+                "int;{};{};1;{}",
+                rules.queen_deadline,
+                uhp.queen_deadline,
+                u8::MAX
+            ),
+            // 0 stands for no limit
+            "MoveLimit" => format!(
+                "int;{};{};0;{}",
+                rules.move_limit.unwrap_or(0),
+                uhp.move_limit.unwrap_or(0),
+                u16::MAX
+            ),
+            "ThreefoldRepetition" => format!(
+                "bool;{};{}",
+                bool_name(rules.threefold_repetition),
+                bool_name(uhp.threefold_repetition)
+            ),
+            _ => return Err(UhpError::Command(format!("Unknown option '{name}'"))),
+        };
+
+        Ok(format!("{name};{option}"))
+    }
+
+    fn set_option(&mut self, name: &str, value: &str) -> Result<(), UhpError> {
+        let invalid = || UhpError::Command(format!("Invalid value '{value}' for {name}"));
+        let rules = &mut self.rules;
+
+        match name {
+            "FirstPlayer" => {
+                rules.first_player = match value {
+                    "White" => Color::White,
+                    "Black" => Color::Black,
+                    _ => return Err(invalid()),
+                }
+            }
+            "TournamentOpening" => {
+                rules.tournament_opening = parse_bool(value).ok_or_else(invalid)?
+            }
+            "QueenDeadline" => {
+                rules.queen_deadline = value
+                    .parse()
+                    .ok()
+                    .filter(|&deadline| deadline > 0)
+                    .ok_or_else(invalid)?
+            }
+            "MoveLimit" => {
+                rules.move_limit = match value.parse().map_err(|_| invalid())? {
+                    0 => None,
+                    limit => Some(limit),
+                }
+            }
+            "ThreefoldRepetition" => {
+                rules.threefold_repetition = parse_bool(value).ok_or_else(invalid)?
+            }
+            _ => return Err(UhpError::Command(format!("Unknown option '{name}'"))),
+        }
+
+        Ok(())
+    }
+
+    fn game_mut(&mut self) -> Result<&mut Game, UhpError> {
+        self.game
+            .as_mut()
+            .ok_or(UhpError::Command("No game in progress".to_string()))
+    }
+
+    fn game_string(&self) -> Result<String, UhpError> {
+        self.game
+            .as_ref()
+            .ok_or(UhpError::Command("No game in progress".to_string()))?
+            .to_game_string()
+            .map_err(|error| UhpError::Command(error.to_string()))
+    }
+}
+
+fn color_name(color: &Color) -> &'static str {
+    match color {
+        Color::White => "White",
+        Color::Black => "Black",
+    }
+}
+
+fn bool_name(value: bool) -> &'static str {
+    match value {
+        true => "True",
+        false => "False",
+    }
+}
+
+fn parse_bool(value: &str) -> Option<bool> {
+    match value {
+        "True" => Some(true),
+        "False" => Some(false),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plays_a_game_through_commands() {
+        let mut engine = Engine::default();
+
+        assert_eq!(engine.handle("play wS1"), "err No game in progress\nok");
+        assert_eq!(engine.handle("newgame"), "Base;NotStarted;White[1]\nok");
+        assert!(!engine.handle("validmoves").contains("wQ")); // tournament rules, like other engines
+        assert_eq!(
+            engine.handle("play wS1"),
+            "Base;InProgress;Black[1];wS1\nok"
+        );
+        assert_eq!(
+            engine.handle("play bS1 wS1-"),
+            "Base;InProgress;White[2];wS1;bS1 wS1-\nok"
+        );
+        assert_eq!(
+            engine.handle("play wQ /wS1"),
+            "Base;InProgress;Black[2];wS1;bS1 wS1-;wQ /wS1\nok"
+        );
+        assert_eq!(
+            engine.handle("play bQ wS1"),
+            "invalidmove Pieces can't be placed on top of others\nok"
+        );
+        assert_eq!(
+            engine.handle("play bA2 bS1-"),
+            "invalidmove Pieces of the same bug are placed from the lowest number up\nok"
+        );
+        assert_eq!(
+            engine.handle("play bQ bS1\\"),
+            "Base;InProgress;White[3];wS1;bS1 wS1-;wQ /wS1;bQ bS1\\\nok"
+        );
+
+        // The queen slides around its spider
+        let valid_moves = engine.handle("validmoves");
+        assert!(valid_moves.contains("wQ -wS1"));
+        assert!(valid_moves.contains("wQ -bQ")); // the cell below wS1, written next to the first neighbor clockwise
+        assert!(!valid_moves.split(';').any(|m| m.starts_with("wS1"))); // pinned between the queen and the black spider
+
+        let best_move = engine.handle("bestmove depth 1");
+        assert!(valid_moves.contains(best_move.trim_end_matches("\nok")));
+        assert_eq!(
+            engine.handle("bestmove in a while"),
+            "err Invalid bestmove limit\nok"
+        );
+
+        assert_eq!(
+            engine.handle("undo 2"),
+            "Base;InProgress;White[2];wS1;bS1 wS1-\nok"
+        );
+        assert_eq!(
+            engine.handle("undo 3"),
+            "err Not that many moves to undo\nok"
+        );
+        assert_eq!(
+            engine.handle("pass"),
+            "invalidmove That's not a valid move\nok"
+        );
+    }
+
+    #[test]
+    fn players_pass_by_themselves() {
+        let mut engine = Engine::default();
+        engine.handle("newgame Base");
+
+        // The white ant closes the gap next to the black bee, leaving black with nothing to do
+        for text in [
+            "wS1", "bS1 wS1/", "wA1 -wS1", "bQ \\bS1", "wQ \\wA1", "bQ -bS1",
+        ] {
+            engine.handle(&format!("play {text}"));
+        }
+        let moves = "wS1;bS1 wS1/;wA1 -wS1;bQ \\bS1;wQ \\wA1;bQ -bS1";
+        let forced = format!("Base;InProgress;Black[4];{moves};wA1 bS1/");
+        let passed = format!("Base;InProgress;White[5];{moves};wA1 bS1/;pass");
+
+        assert_eq!(engine.handle("play wA1 bS1/"), format!("{forced}\nok"));
+        assert_eq!(engine.handle("validmoves"), "pass\nok");
+        assert_eq!(engine.handle("bestmove depth 1"), "pass\nok");
+
+        assert_eq!(engine.handle("play pass"), format!("{passed}\nok"));
+        assert_eq!(engine.handle("undo"), format!("{forced}\nok")); // only the pass is taken back
+        assert_eq!(engine.handle("pass"), format!("{passed}\nok"));
+        assert_eq!(
+            engine.handle("pass"),
+            "invalidmove That's not a valid move\nok"
+        ); // white has moves
+
+        assert_eq!(
+            engine.handle("undo 2"),
+            format!("Base;InProgress;White[4];{moves}\nok")
+        );
+        assert_eq!(
+            engine.handle(&format!("newgame {forced}")),
+            format!("{forced}\nok")
+        );
+        assert_eq!(engine.handle("validmoves"), "pass\nok"); // games continued from a GameString pass the same way
+    }
+
+    #[test]
+    fn names_moves_relative_to_their_neighbors() {
+        let mut engine = Engine::default();
+        engine.handle("newgame Base+MLP");
+
+        for text in ["wS1", "bP -wS1", "wQ wS1\\", "bM \\bP", "wA1 wQ-", "bQ /bP"] {
+            assert!(engine
+                .handle(&format!("play {text}"))
+                .ends_with(&format!(";{text}\nok")));
+        }
+
+        // The white ant goes around the hive next to the black pillbug
+        for text in ["wA1 -wQ", "bM -bP", "wQ wS1-"] {
+            assert!(engine
+                .handle(&format!("play {text}"))
+                .ends_with(&format!(";{text}\nok")));
+        }
+
+        // On black's turn the pillbug throws it, written as a plain move of the ant
+        assert!(engine
+            .handle("play wA1 \\wS1")
+            .starts_with("Base+MLP;InProgress;White[6];"));
+        assert!(matches!(
+            engine.game.as_ref().unwrap().history().last(),
+            Some(Action::Throw { .. })
+        ));
+        assert_eq!(
+            engine.handle("play wA1 -wQ"),
+            "invalidmove That piece was just moved\nok"
+        ); // thrown pieces can't move right away
+
+        assert_eq!(
+            engine.handle("newgame Base+LM"),
+            "err Unknown game type Base+LM\nok"
+        );
+        assert_eq!(
+            engine.handle("newgame Base;InProgress;Black[1];wS1"),
+            "Base;InProgress;Black[1];wS1\nok"
+        ); // a game can also be continued from a GameString
+    }
+
+    #[test]
+    fn changes_the_rules_through_options() {
+        let mut engine = Engine::default();
+
+        assert_eq!(
+            engine.handle("options get FirstPlayer"),
+            "FirstPlayer;enum;White;White;White;Black\nok"
+        );
+        assert_eq!(
+            engine.handle("options set FirstPlayer Black"),
+            "FirstPlayer;enum;Black;White;White;Black\nok"
+        );
+        assert_eq!(
+            engine.handle("options set TournamentOpening False"),
+            "TournamentOpening;bool;False;True\nok"
+        );
+        assert_eq!(
+            engine.handle("options set MoveLimit soon"),
+            "err Invalid value 'soon' for MoveLimit\nok"
+        );
+        assert_eq!(
+            engine.handle("options get Variant"),
+            "err Unknown option 'Variant'\nok"
+        );
+        assert_eq!(engine.handle("options").lines().count(), OPTIONS.len() + 1);
+
+        // Black starts and can place its queen right away, the GameString stays the standard one
+        engine.handle("newgame Base");
+        assert_eq!(engine.handle("play bQ"), "Base;InProgress;White[1];bQ\nok");
+        assert_eq!(
+            engine
+                .handle("newgame Base;InProgress;White[1];wS1")
+                .lines()
+                .next(),
+            Some("err Move 1: It's not your turn")
+        );
+    }
+}